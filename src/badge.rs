@@ -0,0 +1,42 @@
+// Shields-style status badges rendered server-side
+
+pub const COLOR_ONLINE: &str = "#4c1";
+pub const COLOR_OFFLINE: &str = "#e05d44";
+pub const COLOR_UNKNOWN: &str = "#9f9f9f";
+
+/// Renders a flat two-part badge (`label | message`) as an SVG document.
+pub fn render_svg(label: &str, message: &str, color: &str) -> String {
+    let label_w = text_width(label) + 10;
+    let message_w = text_width(message) + 10;
+    let total_w = label_w + message_w;
+    let label = escape_xml(label);
+    let message = escape_xml(message);
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{total_w}" height="20" role="img" aria-label="{label}: {message}">
+<title>{label}: {message}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{total_w}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)"><rect width="{label_w}" height="20" fill="#555"/><rect x="{label_w}" width="{message_w}" height="20" fill="{color}"/><rect width="{total_w}" height="20" fill="url(#s)"/></g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="14">{label}</text>
+<text x="{message_x}" y="14">{message}</text>
+</g>
+</svg>"##,
+        label_x = label_w / 2,
+        message_x = label_w + message_w / 2,
+    )
+}
+
+// Rough Verdana 11px advance, good enough to size the boxes
+fn text_width(s: &str) -> usize {
+    s.chars().count() * 7
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,90 @@
+// Bedrock Edition status via RakNet's "unconnected ping" over UDP. Bedrock servers don't
+// speak the Java status protocol at all, this is what the in-game server list uses instead.
+// https://minecraft.wiki/w/RakNet#Unconnected_Ping
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+
+const UNCONNECTED_PING: u8 = 0x01;
+const UNCONNECTED_PONG: u8 = 0x1c;
+// RakNet's "offline message data id", present in every unconnected packet
+const MAGIC: [u8; 16] = [
+    0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78,
+];
+const CLIENT_GUID: u64 = 0x5745_4253_4552_5645;
+
+#[derive(Debug, Clone)]
+pub struct BedrockStatus {
+    pub motd: String,
+    pub version: String,
+    pub online_players: usize,
+    pub max_players: usize,
+}
+
+/// Pings `addr` once. The caller is expected to wrap this in a timeout.
+pub async fn ping(addr: SocketAddr) -> io::Result<BedrockStatus> {
+    let bind = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(addr).await?;
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let mut request = Vec::with_capacity(33);
+    request.push(UNCONNECTED_PING);
+    request.extend_from_slice(&time.to_be_bytes());
+    request.extend_from_slice(&MAGIC);
+    request.extend_from_slice(&CLIENT_GUID.to_be_bytes());
+    socket.send(&request).await?;
+
+    let mut buf = [0u8; 2048];
+    let n = socket.recv(&mut buf).await?;
+    parse_pong(&buf[..n])
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// Response: id(1) time(8) server guid(8) magic(16) len(2) "MCPE;motd;protocol;version;online;max;..."
+fn parse_pong(buf: &[u8]) -> io::Result<BedrockStatus> {
+    const HEADER: usize = 1 + 8 + 8 + 16;
+
+    if buf.len() < HEADER + 2 || buf[0] != UNCONNECTED_PONG || buf[17..HEADER] != MAGIC {
+        return Err(invalid("bad unconnected pong"));
+    }
+    let len = u16::from_be_bytes([buf[HEADER], buf[HEADER + 1]]) as usize;
+    let text = buf
+        .get(HEADER + 2..HEADER + 2 + len)
+        .ok_or_else(|| invalid("truncated server id string"))?;
+    let text = String::from_utf8_lossy(text);
+
+    let fields: Vec<&str> = text.split(';').collect();
+    if fields.len() < 6 {
+        return Err(invalid("too few fields in server id string"));
+    }
+    let number = |s: &str| {
+        s.trim()
+            .parse::<usize>()
+            .map_err(|_| invalid("player count is not a number"))
+    };
+    // Line 2 of the MOTD (usually the level name) is field 7, when present
+    let motd = match fields.get(7).filter(|l| !l.is_empty()) {
+        Some(line2) => format!("{}\n{}", fields[1], line2),
+        None => fields[1].to_string(),
+    };
+
+    Ok(BedrockStatus {
+        motd,
+        version: fields[3].to_string(),
+        online_players: number(fields[4])?,
+        max_players: number(fields[5])?,
+    })
+}
@@ -0,0 +1,88 @@
+// Server-side rendered player chart for places that can't run the dashboard's JS
+// (Discord embeds, email). Deliberately bare: no text, since plotters would need a
+// system font for that and the point is just "what did activity look like".
+
+use image::{ImageFormat, RgbImage};
+use plotters::prelude::*;
+use std::io::Cursor;
+
+pub const WIDTH: u32 = 800;
+pub const HEIGHT: u32 = 300;
+
+const BACKGROUND: RGBColor = RGBColor(23, 23, 23);
+const GRID: RGBColor = RGBColor(38, 38, 38);
+// Dashboard default accent (#84cc16)
+pub const DEFAULT_LINE: RGBColor = RGBColor(132, 204, 22);
+
+/// `"#rrggbb"` as stored on servers, `None` for anything else.
+pub fn parse_color(hex: &str) -> Option<RGBColor> {
+    let hex = hex.strip_prefix('#').filter(|h| h.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Renders one value per bucket as a filled line chart and returns the PNG bytes.
+/// `None` buckets (no online pings) leave a gap in the line.
+pub fn render_players_png(values: &[Option<f64>], line: RGBColor) -> Result<Vec<u8>, String> {
+    let mut rgb = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut rgb, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&BACKGROUND).map_err(|e| e.to_string())?;
+
+        let max = values.iter().flatten().fold(0.0f64, |a, b| a.max(*b));
+        // Headroom above the peak, and a visible axis even when nobody was ever on
+        let y_max = (max * 1.1).max(1.0);
+        let x_max = values.len().max(2) - 1;
+
+        let mut chart = ChartBuilder::on(&root)
+            .margin(12)
+            .build_cartesian_2d(0..x_max, 0.0..y_max)
+            .map_err(|e| e.to_string())?;
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .y_labels(5)
+            .light_line_style(TRANSPARENT)
+            .bold_line_style(GRID)
+            .axis_style(GRID)
+            .draw()
+            .map_err(|e| e.to_string())?;
+
+        // One series per run of consecutive buckets with data
+        let mut run: Vec<(usize, f64)> = Vec::new();
+        for (i, v) in values.iter().enumerate() {
+            match v {
+                Some(v) => run.push((i, *v)),
+                None => draw_run(&mut chart, &mut run, line)?,
+            }
+        }
+        draw_run(&mut chart, &mut run, line)?;
+
+        root.present().map_err(|e| e.to_string())?;
+    }
+
+    let image = RgbImage::from_raw(WIDTH, HEIGHT, rgb).ok_or("chart buffer has the wrong size")?;
+    let mut png = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png.into_inner())
+}
+
+type Chart<'a, 'b> = ChartContext<
+    'a,
+    BitMapBackend<'b>,
+    Cartesian2d<plotters::coord::types::RangedCoordusize, plotters::coord::types::RangedCoordf64>,
+>;
+
+fn draw_run(chart: &mut Chart, run: &mut Vec<(usize, f64)>, line: RGBColor) -> Result<(), String> {
+    if run.is_empty() {
+        return Ok(());
+    }
+    chart
+        .draw_series(
+            AreaSeries::new(run.drain(..), 0.0, line.mix(0.15)).border_style(line.stroke_width(2)),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
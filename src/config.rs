@@ -0,0 +1,469 @@
+use crate::net::Cidr;
+use crate::password::PasswordPolicy;
+use crate::webhook;
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Serialize, Serializer};
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::warn;
+
+/// Runtime settings read once from the environment at startup.
+/// Serialized as-is by `GET /api/admin/config`, so secrets must be `#[serde(skip)]`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Config {
+    /// SQLite connection string (`DATABASE_URL`), derived from `data_dir` when unset.
+    pub database_url: String,
+    /// Directory for `sqlite.db` when `DATABASE_URL` isn't given (`DATA_DIR`).
+    pub data_dir: Option<String>,
+    /// Display name of this dashboard (`INSTANCE_NAME`), shown in the header.
+    pub instance_name: Option<String>,
+    /// `host:port` to listen on, or `unix:/path/to.sock` for a Unix domain socket.
+    pub bind_addr: String,
+    /// Upper bound on tracked servers, `None` means unlimited (`MAX_SERVERS=0`).
+    pub max_servers: Option<i64>,
+    /// How long the assembled `/api/servers` list is reused, zero disables caching.
+    #[serde(rename = "servers_cache_secs", serialize_with = "as_secs")]
+    pub servers_cache_ttl: Duration,
+    /// Seed the `admin` user on startup (`CREATE_DEFAULT_ADMIN=false` to skip).
+    pub create_default_admin: bool,
+    /// Overrides SQLite's `wal_autocheckpoint` page count when set.
+    pub wal_autocheckpoint_pages: Option<u32>,
+    /// Max connections of the read-only pool that serves SELECTs.
+    pub db_read_pool_size: u32,
+    /// Run `wal_checkpoint(TRUNCATE)` on every maintenance pass.
+    pub wal_checkpoint_truncate: bool,
+    /// How often old pings and expired sessions are deleted.
+    #[serde(rename = "maintenance_interval_secs", serialize_with = "as_secs")]
+    pub maintenance_interval: Duration,
+    /// Admin sessions are valid for this long after login.
+    #[serde(rename = "session_ttl_secs", serialize_with = "as_secs")]
+    pub session_ttl: Duration,
+    /// Logged-in sessions per admin, a new login ends the oldest beyond this.
+    pub max_sessions_per_admin: i64,
+    /// Reported player counts above this are clamped before being stored.
+    pub max_player_count: i64,
+    /// Longer MOTDs and version strings are cut to this many characters before storing.
+    pub max_motd_len: usize,
+    pub max_version_len: usize,
+    /// API requests running longer than this are aborted with a 504.
+    #[serde(rename = "request_timeout_secs", serialize_with = "as_secs")]
+    pub request_timeout: Duration,
+    /// After a shutdown signal, how long open connections get to finish before they're dropped.
+    #[serde(rename = "shutdown_timeout_secs", serialize_with = "as_secs")]
+    pub shutdown_timeout: Duration,
+    /// Ping every server once at boot, before the interval-aligned loop starts.
+    pub ping_on_startup: bool,
+    /// How long a ping may take to open the TCP connection.
+    #[serde(rename = "ping_connect_timeout_secs", serialize_with = "as_secs")]
+    pub ping_connect_timeout: Duration,
+    /// How long a ping may wait for the status answer once connected.
+    #[serde(rename = "ping_read_timeout_secs", serialize_with = "as_secs")]
+    pub ping_read_timeout: Duration,
+    /// Spread each cycle's pings over this many seconds (stable offset per server).
+    pub ping_jitter_secs: u64,
+    /// Networks allowed to reach admin/auth routes, empty means no restriction.
+    pub admin_ip_allowlist: Vec<Cidr>,
+    /// Trust `X-Forwarded-For` / `X-Real-IP` from a reverse proxy for the client address.
+    pub trust_proxy: bool,
+    /// Public status page mode: login and every admin/mutating endpoint answer 403.
+    pub read_only: bool,
+    /// Private dashboard mode: every read endpoint except health needs an admin session.
+    pub auth_required_for_reads: bool,
+    /// `SameSite` attribute of the session cookie.
+    pub cookie_same_site: SameSite,
+    /// `Domain` attribute of the session cookie, host-only when unset.
+    pub cookie_domain: Option<String>,
+    /// Where up/down alerts go unless a server has its own webhook. Contains a token.
+    #[serde(skip)]
+    pub webhook_url: Option<String>,
+    /// When the session cookie gets `Secure`. `Auto` is resolved to `Always` for production
+    /// and `SameSite=None`, otherwise it follows the request's `X-Forwarded-Proto`.
+    pub cookie_secure: CookieSecure,
+    /// Origins allowed to call the API cross-origin, `*` for any; empty disables CORS.
+    pub cors_origins: Vec<String>,
+    /// Bucket upper bounds (ms) of the latency histograms in `/api/metrics`, ascending.
+    pub latency_buckets_ms: Vec<f64>,
+    /// Upper bound on points a downsampled (week/month) history returns.
+    pub max_history_points: usize,
+    /// The pinger counts as stalled when no cycle finished for this long.
+    pub stale_after_secs: i64,
+    /// Consecutive offline pings before a server counts as down (and alerts).
+    pub down_confirm_pings: i64,
+    /// Consecutive online pings before a down server counts as back up.
+    pub up_confirm_pings: i64,
+    /// Gaps between pings longer than this count as "no data" in uptime, not downtime.
+    pub uptime_gap_secs: i64,
+    /// Online/offline flips per day above which a server is reported as flapping.
+    pub flap_threshold_per_day: u32,
+    /// Fold consecutive offline scheduled pings into one row instead of storing each.
+    pub collapse_repeated_offline: bool,
+    /// Leave "Anonymous Player" and similar stand-ins out of `/players`.
+    pub hide_placeholder_players: bool,
+    /// Don't serve the frontend, `/` answers with a JSON description of the API instead.
+    pub api_only: bool,
+    /// Rules new admin passwords must satisfy.
+    pub password_policy: PasswordPolicy,
+    /// Argon2id cost for new password hashes, existing hashes keep the cost they were made with.
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    /// Log line format (`LOG_FORMAT`), `json` for log pipelines.
+    pub log_format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+impl FromStr for SameSite {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "strict" => Ok(SameSite::Strict),
+            "lax" => Ok(SameSite::Lax),
+            "none" => Ok(SameSite::None),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSecure {
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for CookieSecure {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(CookieSecure::Auto),
+            "always" => Ok(CookieSecure::Always),
+            "never" => Ok(CookieSecure::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let max_servers = match env_parse::<i64>("MAX_SERVERS").unwrap_or(500) {
+            n if n <= 0 => None,
+            n => Some(n),
+        };
+
+        let servers_cache_ttl = Duration::from_secs(env_parse("SERVERS_CACHE_SECS").unwrap_or(5));
+
+        let cookie_same_site = env_parse("COOKIE_SAMESITE").unwrap_or(SameSite::Strict);
+        let is_prod = env::var("APP_ENV").unwrap_or_default() == "production";
+        let cookie_secure = match env_parse("COOKIE_SECURE").unwrap_or(CookieSecure::Auto) {
+            // Browsers drop SameSite=None cookies that aren't Secure
+            setting if cookie_same_site == SameSite::None => {
+                if setting != CookieSecure::Always && !is_prod {
+                    warn!(
+                        "COOKIE_SAMESITE=None requires Secure cookies, enabling Secure (HTTPS only)."
+                    );
+                }
+                CookieSecure::Always
+            }
+            CookieSecure::Auto if is_prod => CookieSecure::Always,
+            setting => setting,
+        };
+
+        let read_only = env_flag("READ_ONLY").unwrap_or(false);
+        let auth_required_for_reads = env_flag("AUTH_REQUIRED_FOR_READS").unwrap_or(false);
+        if read_only && auth_required_for_reads {
+            warn!(
+                "READ_ONLY disables login, with AUTH_REQUIRED_FOR_READS nobody can read anything."
+            );
+        }
+
+        let data_dir = env::var("DATA_DIR")
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .ok()
+            .filter(|v| !v.is_empty());
+        let database_url = match (env::var("DATABASE_URL"), &data_dir) {
+            (Ok(url), Some(_)) => {
+                warn!("DATABASE_URL is set, ignoring DATA_DIR.");
+                url
+            }
+            (Ok(url), None) => url,
+            (Err(_), Some(dir)) => format!("sqlite://{}/sqlite.db", dir),
+            (Err(_), None) => "sqlite://sqlite.db".to_string(),
+        };
+
+        Self {
+            database_url,
+            data_dir,
+            instance_name: env::var("INSTANCE_NAME")
+                .map(|v| v.trim().to_string())
+                .ok()
+                .filter(|v| !v.is_empty()),
+            bind_addr: env::var("BIND_ADDR")
+                .map(|v| v.trim().to_string())
+                .ok()
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| "0.0.0.0:3000".to_string()),
+            max_servers,
+            servers_cache_ttl,
+            create_default_admin: env_flag("CREATE_DEFAULT_ADMIN").unwrap_or(true),
+            wal_autocheckpoint_pages: env_parse("WAL_AUTOCHECKPOINT_PAGES"),
+            db_read_pool_size: env_bounded("DB_READ_POOL_SIZE", 1..=64).unwrap_or(8),
+            wal_checkpoint_truncate: env_flag("WAL_CHECKPOINT_TRUNCATE").unwrap_or(true),
+            maintenance_interval: Duration::from_secs(
+                env_bounded("MAINTENANCE_INTERVAL_SECS", 60..=7 * 24 * 3600).unwrap_or(3600),
+            ),
+            // 30 days
+            session_ttl: Duration::from_secs(
+                env_bounded("SESSION_TTL_SECS", 300..=365 * 24 * 3600).unwrap_or(30 * 24 * 3600),
+            ),
+            max_sessions_per_admin: env_bounded("MAX_SESSIONS_PER_ADMIN", 1..=100).unwrap_or(10),
+            max_player_count: env_parse::<i64>("MAX_PLAYER_COUNT")
+                .filter(|n| *n > 0)
+                .unwrap_or(1_000_000),
+            max_motd_len: env_bounded("MAX_MOTD_LENGTH", 16..=65_536).unwrap_or(1000),
+            max_version_len: env_bounded("MAX_VERSION_LENGTH", 16..=4096).unwrap_or(100),
+            shutdown_timeout: Duration::from_secs(
+                env_parse::<u64>("SHUTDOWN_TIMEOUT_SECS").unwrap_or(10),
+            ),
+            request_timeout: Duration::from_secs(
+                env_parse::<u64>("REQUEST_TIMEOUT_SECS")
+                    .filter(|n| *n > 0)
+                    .unwrap_or(30),
+            ),
+            ping_on_startup: env_flag("PING_ON_STARTUP").unwrap_or(true),
+            ping_connect_timeout: Duration::from_secs(
+                env_bounded("PING_CONNECT_TIMEOUT_SECS", 1..=30).unwrap_or(3),
+            ),
+            ping_read_timeout: Duration::from_secs(
+                env_bounded("PING_READ_TIMEOUT_SECS", 1..=30).unwrap_or(3),
+            ),
+            // Kept a minute under the ping interval so cycles can't overlap
+            ping_jitter_secs: env_bounded("PING_JITTER_SECS", 0..=crate::PING_INTERVAL_SECS - 60)
+                .unwrap_or(0),
+            admin_ip_allowlist: parse_allowlist(
+                &env::var("ADMIN_IP_ALLOWLIST").unwrap_or_default(),
+            ),
+            trust_proxy: env_flag("TRUST_PROXY").unwrap_or(false),
+            read_only,
+            auth_required_for_reads,
+            cookie_same_site,
+            cookie_domain: parse_cookie_domain(&env::var("COOKIE_DOMAIN").unwrap_or_default()),
+            webhook_url: parse_webhook_url(&env::var("WEBHOOK_URL").unwrap_or_default()),
+            cookie_secure,
+            latency_buckets_ms: parse_latency_buckets(
+                &env::var("LATENCY_BUCKETS_MS").unwrap_or_default(),
+            ),
+            max_history_points: env_bounded("MAX_HISTORY_POINTS", 10..=100_000).unwrap_or(1000),
+            // Two intervals, one late cycle isn't worth a warning
+            stale_after_secs: env_parse::<i64>("STALE_AFTER_SECS")
+                .filter(|n| *n > 0)
+                .unwrap_or(1200),
+            down_confirm_pings: env_bounded("DOWN_CONFIRM_PINGS", 1..=20).unwrap_or(2),
+            up_confirm_pings: env_bounded("UP_CONFIRM_PINGS", 1..=20).unwrap_or(1),
+            // Default: two missed ping intervals
+            uptime_gap_secs: env_parse::<i64>("UPTIME_GAP_SECS")
+                .filter(|n| *n > 0)
+                .unwrap_or(1200),
+            cors_origins: env::var("CORS_ORIGINS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|o| o.trim().trim_end_matches('/').to_string())
+                .filter(|o| !o.is_empty())
+                .collect(),
+            api_only: env_flag("API_ONLY").unwrap_or(false),
+            password_policy: PasswordPolicy {
+                min_length: env_bounded("PASSWORD_MIN_LENGTH", 1..=128).unwrap_or(8),
+                require_digit: env_flag("PASSWORD_REQUIRE_DIGIT").unwrap_or(false),
+                require_symbol: env_flag("PASSWORD_REQUIRE_SYMBOL").unwrap_or(false),
+                reject_common: env_flag("PASSWORD_REJECT_COMMON").unwrap_or(true),
+            },
+            // 1 MiB .. 4 GiB; the lower bound also covers argon2's 8 KiB per lane minimum
+            argon2_memory_kib: env_bounded("ARGON2_MEMORY_KIB", 1024..=4 * 1024 * 1024)
+                .unwrap_or(Params::DEFAULT_M_COST),
+            argon2_iterations: env_bounded("ARGON2_ITERATIONS", 1..=50)
+                .unwrap_or(Params::DEFAULT_T_COST),
+            argon2_parallelism: env_bounded("ARGON2_PARALLELISM", 1..=16)
+                .unwrap_or(Params::DEFAULT_P_COST),
+            flap_threshold_per_day: env_parse::<u32>("FLAP_THRESHOLD_PER_DAY")
+                .filter(|n| *n > 0)
+                .unwrap_or(6),
+            collapse_repeated_offline: env_flag("COLLAPSE_REPEATED_OFFLINE").unwrap_or(false),
+            hide_placeholder_players: env_flag("HIDE_PLACEHOLDER_PLAYERS").unwrap_or(true),
+            log_format: env_parse("LOG_FORMAT").unwrap_or_default(),
+        }
+    }
+}
+
+impl Config {
+    /// Argon2id hasher with the configured cost.
+    pub fn argon2(&self) -> Argon2<'static> {
+        let params = Params::new(
+            self.argon2_memory_kib,
+            self.argon2_iterations,
+            self.argon2_parallelism,
+            None,
+        )
+        .expect("argon2 parameters are bounds-checked in from_env");
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+    }
+}
+
+// A typo here must not silently open the admin panel to everyone, so bad entries are fatal
+fn parse_allowlist(raw: &str) -> Vec<Cidr> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse()
+                .unwrap_or_else(|e| panic!("Invalid ADMIN_IP_ALLOWLIST entry: {}", e))
+        })
+        .collect()
+}
+
+fn as_secs<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u64(d.as_secs())
+}
+
+// Goes straight into Set-Cookie, so anything that could inject attributes is fatal
+fn parse_cookie_domain(raw: &str) -> Option<String> {
+    let domain = raw.trim();
+    if domain.is_empty() {
+        return None;
+    }
+    if domain
+        .chars()
+        .any(|c| !(c.is_ascii_alphanumeric() || c == '.' || c == '-'))
+    {
+        panic!("Invalid COOKIE_DOMAIN: {:?}", raw);
+    }
+    Some(domain.to_string())
+}
+
+fn parse_webhook_url(raw: &str) -> Option<String> {
+    let url = raw.trim();
+    if url.is_empty() {
+        return None;
+    }
+    match webhook::validate_url(url) {
+        Ok(()) => Some(url.to_string()),
+        Err(msg) => {
+            warn!("Ignoring WEBHOOK_URL: {}", msg);
+            None
+        }
+    }
+}
+
+// Parses an env var, warning (instead of silently defaulting) when it's set but malformed
+fn env_parse<T: FromStr>(key: &str) -> Option<T> {
+    let raw = env::var(key).ok()?;
+    match raw.trim().parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            warn!("Ignoring invalid value for {}: {:?}", key, raw);
+            None
+        }
+    }
+}
+
+// Roughly LAN, same region, across an ocean, and the slow tail up to the read timeout
+const DEFAULT_LATENCY_BUCKETS_MS: [f64; 10] = [
+    5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0,
+];
+
+fn parse_latency_buckets(raw: &str) -> Vec<f64> {
+    if raw.trim().is_empty() {
+        return DEFAULT_LATENCY_BUCKETS_MS.to_vec();
+    }
+    let bounds: Option<Vec<f64>> = raw
+        .split(',')
+        .map(|s| {
+            s.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|b| b.is_finite() && *b > 0.0)
+        })
+        .collect();
+    match bounds {
+        Some(mut bounds) => {
+            bounds.sort_by(f64::total_cmp);
+            bounds.dedup();
+            bounds
+        }
+        None => {
+            warn!("Ignoring invalid value for LATENCY_BUCKETS_MS: {:?}", raw);
+            DEFAULT_LATENCY_BUCKETS_MS.to_vec()
+        }
+    }
+}
+
+// Like `env_parse`, but values outside `range` are reported and ignored
+fn env_bounded<T>(key: &str, range: std::ops::RangeInclusive<T>) -> Option<T>
+where
+    T: FromStr + PartialOrd + std::fmt::Display,
+{
+    let v = env_parse::<T>(key)?;
+    if range.contains(&v) {
+        Some(v)
+    } else {
+        warn!(
+            "Ignoring {}={}, it must be between {} and {}",
+            key,
+            v,
+            range.start(),
+            range.end()
+        );
+        None
+    }
+}
+
+// Accepts the usual spellings of a boolean: true/false, 1/0, yes/no, on/off
+fn env_flag(key: &str) -> Option<bool> {
+    let raw = env::var(key).ok()?;
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => {
+            warn!("Ignoring invalid value for {}: {:?}", key, raw);
+            None
+        }
+    }
+}
@@ -1,9 +1,135 @@
-use serde::Serialize;
-use sqlx::{Error, Row, Sqlite, SqlitePool, migrate::MigrateDatabase};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Error, Row, Sqlite, SqliteConnection, SqlitePool,
+    migrate::MigrateDatabase,
+    query::Query,
+    sqlite::{SqliteArguments, SqliteConnectOptions, SqlitePoolOptions},
+};
+use std::str::FromStr;
+use tokio::sync::mpsc;
+use tracing::info;
+
+/// Database failures, sorted into what a caller can act on.
+#[derive(Debug)]
+pub enum DbError {
+    /// A query that must return a row found none.
+    NotFound,
+    /// A UNIQUE / FOREIGN KEY / CHECK constraint rejected the write.
+    Conflict(String),
+    /// The pool or the database file is unavailable (I/O, pool timeout or shutdown).
+    Connection(Error),
+    Other(Error),
+}
+
+impl From<Error> for DbError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::RowNotFound => DbError::NotFound,
+            Error::Database(ref db)
+                if db.is_unique_violation()
+                    || db.is_foreign_key_violation()
+                    || db.is_check_violation() =>
+            {
+                DbError::Conflict(db.message().to_string())
+            }
+            Error::Io(_) | Error::PoolTimedOut | Error::PoolClosed | Error::WorkerCrashed => {
+                DbError::Connection(e)
+            }
+            _ => DbError::Other(e),
+        }
+    }
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::NotFound => write!(f, "not found"),
+            DbError::Conflict(msg) => write!(f, "conflict: {}", msg),
+            DbError::Connection(e) => write!(f, "database unavailable: {}", e),
+            DbError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl DbError {
+    /// The database file (or its directory) can't be created, opened or written to,
+    /// which at startup almost always means a wrong path or missing permissions.
+    pub fn is_unwritable(&self) -> bool {
+        match self {
+            DbError::Connection(Error::Io(_)) => true,
+            DbError::Other(Error::Database(db)) => {
+                // Extended codes keep the primary one in the low byte
+                let primary = db.code().and_then(|c| c.parse::<i32>().ok()).unwrap_or(0) & 0xff;
+                primary == SQLITE_READONLY || primary == SQLITE_CANTOPEN
+            }
+            _ => false,
+        }
+    }
+}
+
+const SQLITE_READONLY: i32 = 8;
+const SQLITE_CANTOPEN: i32 = 14;
+
+/// Filesystem path of a `sqlite:` URL, for messages.
+pub fn database_path(db_url: &str) -> String {
+    SqliteConnectOptions::from_str(db_url)
+        .map(|o| o.get_filename().display().to_string())
+        .unwrap_or_else(|_| db_url.to_string())
+}
 
 #[derive(Clone)]
 pub struct Database {
+    // Migrations and every write go here
     pool: SqlitePool,
+    // Read-only connections for SELECTs, so history queries don't queue behind the pinger.
+    // WAL lets them read while a write is in progress.
+    read_pool: SqlitePool,
+}
+
+/// Connection counts of the two pools, for `/api/health`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PoolStats {
+    pub write: PoolUsage,
+    pub read: PoolUsage,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PoolUsage {
+    /// Open connections, idle or in use.
+    pub size: u32,
+    pub idle: usize,
+    pub max: u32,
+}
+
+impl PoolUsage {
+    fn of(pool: &SqlitePool) -> Self {
+        Self {
+            size: pool.size(),
+            idle: pool.num_idle(),
+            max: pool.options().get_max_connections(),
+        }
+    }
+}
+
+/// Average players for one (day of week, hour of day) slot, days counted from Sunday = 0.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct HourlyActivity {
+    pub weekday: i64,
+    pub hour: i64,
+    pub samples: i64,
+    pub avg_players: f64,
+}
+
+/// Players summed over every server's latest ping in one time bucket.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct NetworkBucket {
+    pub bucket: i64,
+    pub players: i64,
+    // Servers with a ping in the bucket
+    pub servers: i64,
 }
 
 #[derive(Debug, Clone, Serialize, sqlx::FromRow)]
@@ -13,6 +139,98 @@ pub struct Server {
     pub address: String,
     pub port: i64,
     pub created_at: String,
+
+    // UDP Query (GameSpy4) for the full player list, off unless the target enables it
+    pub enable_query: bool,
+    pub query_port: Option<i64>,
+    // Protocol number sent in the status handshake, None = craftping's default (-1)
+    pub protocol_hint: Option<i64>,
+    // Forge server: the handshake host carries the FML marker (see `handshake::forge_host`)
+    pub modded: bool,
+    // Comma separated, aggregated from server_tags (see `Server::tag_list`)
+    pub tags: Option<String>,
+    // Unix time until which alerts for this server are silenced
+    pub muted_until: Option<i64>,
+    pub edition: Edition,
+    pub check_type: CheckType,
+    // "#rrggbb" accent for the UI, None = frontend default
+    pub color: Option<String>,
+    // Display position, lowest first. Falls back to the id until servers are reordered
+    pub sort_order: i64,
+    // Private servers are only visible to logged-in admins
+    pub is_public: bool,
+    // Listed before all unpinned servers, sort_order applies within each group
+    pub is_pinned: bool,
+    // Alerts for this server go here instead of WEBHOOK_URL
+    pub webhook_url: Option<String>,
+    // Alert once players_online climbs to this many, None = no capacity alerts
+    pub player_alert_threshold: Option<i64>,
+    // State the last up/down alert was about, after DOWN/UP_CONFIRM_PINGS agreeing pings.
+    // None until the first confirmation, which doesn't alert
+    pub confirmed_online: Option<bool>,
+}
+
+impl Server {
+    /// Tags in alphabetical order (group_concat doesn't guarantee one).
+    pub fn tag_list(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self
+            .tags
+            .as_deref()
+            .map(|t| t.split(',').map(str::to_string).collect())
+            .unwrap_or_default();
+        tags.sort();
+        tags
+    }
+
+    /// Seconds of mute left at `now`, `None` when alerts aren't muted.
+    pub fn mute_remaining(&self, now: i64) -> Option<i64> {
+        self.muted_until.map(|t| t - now).filter(|left| *left > 0)
+    }
+}
+
+/// A server to recreate with [`Database::restore_servers`]. `settings` holds everything
+/// but the fields below.
+#[derive(Debug, Clone)]
+pub struct RestoredServer {
+    pub name: String,
+    pub address: String,
+    pub port: i64,
+    pub edition: Edition,
+    pub is_pinned: bool,
+    pub settings: ServerUpdate,
+}
+
+/// Optional changes for [`Database::update_server`], `None` leaves the column as is.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ServerUpdate {
+    pub name: Option<String>,
+    pub enable_query: Option<bool>,
+    // Outer None = unchanged, Some(None) = clear (fall back to the game port)
+    #[serde(default, deserialize_with = "double_option")]
+    pub query_port: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub protocol_hint: Option<Option<i64>>,
+    pub modded: Option<bool>,
+    // Replaces the whole set when present
+    pub tags: Option<Vec<String>>,
+    pub edition: Option<Edition>,
+    pub check_type: Option<CheckType>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub color: Option<Option<String>>,
+    pub is_public: Option<bool>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub webhook_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub player_alert_threshold: Option<Option<i64>>,
+}
+
+// Lets a JSON `null` mean "clear" rather than "absent"
+fn double_option<'de, T, D>(d: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    Option::<T>::deserialize(d).map(Some)
 }
 
 #[derive(Debug, Clone, Serialize, sqlx::FromRow)]
@@ -20,15 +238,248 @@ pub struct PingResult {
     pub id: i64,
     pub server_id: i64,
     pub pinged_at: String,
+    // Same instant as `pinged_at`, used for all time math
+    #[serde(skip_serializing)]
+    pub pinged_at_epoch: i64,
     pub online: bool,
 
     // frontend expects: player_count
+    // On averaged points this is the average rounded half-up, see `players_avg`
     #[serde(rename = "player_count")]
     pub players_online: Option<i64>,
 
+    // Unrounded average (one decimal) when this point stands for several pings, only set
+    // on downsampled API output. Stored compacted rows keep just the rounded `player_count`.
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub players_avg: Option<f64>,
+
+    pub players_max: Option<i64>,
+    pub version: Option<String>,
+    pub motd: Option<String>,
+
+    // Reachable over TCP, but the status response couldn't be understood
+    pub parse_error: bool,
+
+    // Reported player count before clamping, only set when it was clamped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub players_online_raw: Option<i64>,
+
+    // Averaged stand-in for several original pings, written by `compact_pings`
+    pub compacted: bool,
+
+    pub source: PingSource,
+
+    // The favicon differs from the one in the previous successful ping
+    pub favicon_changed: bool,
+
+    // Set on offline pings written since failures were classified
+    pub error_kind: Option<PingErrorKind>,
+
+    // Identical offline pings folded into this row (COLLAPSE_REPEATED_OFFLINE), see
+    // `expand_repeats`. Always 0 in API output since reads expand them again.
+    #[serde(skip_serializing)]
+    pub repeat_count: i64,
+    #[serde(skip_serializing)]
+    pub last_repeated_epoch: Option<i64>,
+}
+
+impl PingResult {
+    /// Timestamp of the newest ping this row stands for.
+    pub fn latest_epoch(&self) -> i64 {
+        self.last_repeated_epoch
+            .filter(|_| self.repeat_count > 0)
+            .unwrap_or(self.pinged_at_epoch)
+    }
+}
+
+/// Turns each collapsed row back into `repeat_count + 1` rows, spread evenly between the
+/// first and the last repeat (scheduled pings are evenly spaced, so this is what was skipped).
+/// Copies keep the id of the stored row.
+fn expand_repeats(rows: Vec<PingResult>) -> Vec<PingResult> {
+    let extra: i64 = rows.iter().map(|p| p.repeat_count).sum();
+    if extra == 0 {
+        return rows;
+    }
+    let mut out = Vec::with_capacity(rows.len() + extra as usize);
+    for mut p in rows {
+        let (n, first, last) = (p.repeat_count, p.pinged_at_epoch, p.latest_epoch());
+        p.repeat_count = 0;
+        p.last_repeated_epoch = None;
+        out.push(p.clone());
+        for k in 1..=n {
+            let mut copy = p.clone();
+            copy.pinged_at_epoch = first + (last - first) * k / n;
+            copy.pinged_at = chrono::DateTime::from_timestamp(copy.pinged_at_epoch, 0)
+                .unwrap_or_default()
+                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+            out.push(copy);
+        }
+    }
+    out
+}
+
+/// Which Minecraft edition a server runs, decides how it is pinged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum Edition {
+    /// TCP status ping (Server List Ping).
+    #[default]
+    Java,
+    /// RakNet unconnected ping over UDP.
+    Bedrock,
+}
+
+impl Edition {
+    /// Port the edition's dedicated server listens on out of the box.
+    pub fn default_port(self) -> i64 {
+        match self {
+            Edition::Java => 25565,
+            Edition::Bedrock => 19132,
+        }
+    }
+}
+
+/// What a ping checks. Anything but `Minecraft` ignores the edition.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum CheckType {
+    /// Status ping of the server's edition.
+    #[default]
+    Minecraft,
+    /// Plain TCP connect, for services next to the game server (web, database, ...).
+    Tcp,
+}
+
+/// Who produced a ping row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum PingSource {
+    /// The background pinger (interval-aligned, so safe for uptime math).
+    #[default]
+    Scheduled,
+    /// An admin hitting the ping endpoint.
+    Manual,
+    /// Pushed in from outside, e.g. the bulk import endpoint.
+    Agent,
+}
+
+/// Why an offline ping failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum PingErrorKind {
+    /// The hostname didn't resolve, even after a retry. Says nothing about the server itself.
+    Dns,
+    /// No complete answer within the ping timeout. Only on rows from before the connect
+    /// and read phases were timed separately.
+    Timeout,
+    /// The TCP connection wasn't established within `PING_CONNECT_TIMEOUT_SECS`.
+    ConnectTimeout,
+    /// Connected (or sent, for Bedrock), but no status answer within `PING_READ_TIMEOUT_SECS`.
+    ReadTimeout,
+    /// Refused, reset or another I/O error while connecting or talking to the server.
+    Connect,
+}
+
+/// A ping row to be written, see [`Database::insert_ping_result`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct NewPing {
+    pub server_id: i64,
+    // Defaults to now; set when backfilling history
+    pub pinged_at: Option<String>,
+    pub online: bool,
+    pub latency_ms: Option<i64>,
+    pub players_online: Option<i64>,
     pub players_max: Option<i64>,
     pub version: Option<String>,
     pub motd: Option<String>,
+    pub parse_error: bool,
+    pub players_online_raw: Option<i64>,
+    #[serde(skip)]
+    pub query_data: Option<String>,
+    // JSON list of the mods a Forge server reported
+    #[serde(skip)]
+    pub mods: Option<String>,
+    // JSON player sample as the server sent it, `[]` when online without one
+    #[serde(skip)]
+    pub player_sample: Option<String>,
+    #[serde(skip)]
+    pub compacted: bool,
+    #[serde(skip)]
+    pub source: PingSource,
+    // Hex SHA-256 of the favicon, "" when the server sends none, None when unknown
+    #[serde(skip)]
+    pub favicon_hash: Option<String>,
+    #[serde(skip)]
+    pub favicon_changed: bool,
+    #[serde(skip)]
+    pub error_kind: Option<PingErrorKind>,
+}
+
+impl NewPing {
+    /// Copy of a stored ping to re-insert as a compacted point (latency and query data are dropped).
+    pub fn compacted_from(p: &PingResult) -> Self {
+        Self {
+            server_id: p.server_id,
+            pinged_at: Some(p.pinged_at.clone()),
+            online: p.online,
+            players_online: p.players_online,
+            players_max: p.players_max,
+            version: p.version.clone(),
+            motd: p.motd.clone(),
+            parse_error: p.parse_error,
+            players_online_raw: p.players_online_raw,
+            compacted: true,
+            source: p.source,
+            error_kind: p.error_kind,
+            ..Default::default()
+        }
+    }
+}
+
+// Column list matching `Server`, only valid with `FROM servers` (the tags subquery refers to it)
+const SERVER_COLUMNS: &str = "id, name, address, port, created_at, enable_query, query_port, protocol_hint, modded, muted_until, edition, check_type, color, \
+     COALESCE(sort_order, id) AS sort_order, is_public, is_pinned, webhook_url, \
+     player_alert_threshold, confirmed_online, \
+     (SELECT group_concat(tag, ',') FROM server_tags WHERE server_id = servers.id) AS tags";
+
+// Column list matching `PingResult`, shared by every ping query
+const PING_COLUMNS: &str = "id, server_id, pinged_at, pinged_at_epoch, online, players_online, players_max, version, motd, parse_error, players_online_raw, compacted, source, favicon_changed, error_kind, repeat_count, last_repeated_epoch";
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ServerEvent {
+    pub id: i64,
+    pub server_id: i64,
+    pub kind: String,
+    pub detail: Option<String>,
+    pub created_at: String,
+}
+
+/// A server whose most recent ping was offline.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OfflineServer {
+    pub id: i64,
+    pub name: String,
+    pub address: String,
+    pub port: i64,
+    // First offline ping after the last online one
+    pub down_since_epoch: i64,
+    pub is_public: bool,
+}
+
+/// Ping counts aggregated into one fixed-width time bucket.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct BucketCounts {
+    pub bucket: i64,
+    pub samples: i64,
+    pub online: i64,
+    // Average over the online pings only, None if there weren't any
+    pub avg_players: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, sqlx::FromRow)]
@@ -39,17 +490,170 @@ pub struct AdminUser {
     pub created_at: String,
 }
 
+// The UPDATE behind `update_server`, shared with `restore_servers` so both run it
+// inside their own transaction
+async fn apply_server_update(
+    conn: &mut SqliteConnection,
+    id: i64,
+    update: &ServerUpdate,
+) -> Result<bool, DbError> {
+    let res = sqlx::query(
+        r#"
+        UPDATE servers SET
+            name = COALESCE(?, name),
+            enable_query = COALESCE(?, enable_query),
+            query_port = CASE WHEN ? THEN ? ELSE query_port END,
+            protocol_hint = CASE WHEN ? THEN ? ELSE protocol_hint END,
+            modded = COALESCE(?, modded),
+            edition = COALESCE(?, edition),
+            check_type = COALESCE(?, check_type),
+            color = CASE WHEN ? THEN ? ELSE color END,
+            is_public = COALESCE(?, is_public),
+            webhook_url = CASE WHEN ? THEN ? ELSE webhook_url END,
+            player_alert_threshold =
+                CASE WHEN ? THEN ? ELSE player_alert_threshold END
+        WHERE id = ?
+        "#,
+    )
+    .bind(&update.name)
+    .bind(update.enable_query)
+    .bind(update.query_port.is_some())
+    .bind(update.query_port.flatten())
+    .bind(update.protocol_hint.is_some())
+    .bind(update.protocol_hint.flatten())
+    .bind(update.modded)
+    .bind(update.edition)
+    .bind(update.check_type)
+    .bind(update.color.is_some())
+    .bind(update.color.clone().flatten().map(|c| normalize_color(&c)))
+    .bind(update.is_public)
+    .bind(update.webhook_url.is_some())
+    .bind(update.webhook_url.clone().flatten())
+    .bind(update.player_alert_threshold.is_some())
+    .bind(update.player_alert_threshold.flatten())
+    .bind(id)
+    .execute(&mut *conn)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    if let Some(tags) = &update.tags {
+        sqlx::query("DELETE FROM server_tags WHERE server_id = ?")
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        for tag in tags {
+            sqlx::query("INSERT OR IGNORE INTO server_tags (server_id, tag) VALUES (?, ?)")
+                .bind(id)
+                .bind(normalize_tag(tag))
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+    Ok(true)
+}
+
+/// Tags are case-insensitive labels, stored lowercase and without surrounding whitespace.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_ascii_lowercase()
+}
+
+/// Expands `#abc` to `#aabbcc` and lowercases, so stored colors have one shape.
+/// Expects an already validated color (see `is_valid_color` in main).
+pub fn normalize_color(color: &str) -> String {
+    let hex = color.trim().trim_start_matches('#').to_ascii_lowercase();
+    if hex.len() == 3 {
+        let long: String = hex.chars().flat_map(|c| [c, c]).collect();
+        format!("#{}", long)
+    } else {
+        format!("#{}", hex)
+    }
+}
+
+/// Canonical form of a hostname so `Example.COM.` and `example.com` are the same endpoint.
+/// ASCII-only lowercasing to match SQLite's `lower()` used on older rows.
+pub fn normalize_address(address: &str) -> String {
+    address.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+// Makes user input match literally inside a LIKE pattern (used with ESCAPE '\')
+fn escape_like(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// Unix timestamp `secs` seconds ago
+fn now_minus(secs: u64) -> i64 {
+    chrono::Utc::now().timestamp() - secs as i64
+}
+
+fn ping_insert_query(ping: &NewPing) -> Query<'_, Sqlite, SqliteArguments<'_>> {
+    sqlx::query(
+        r#"
+        INSERT INTO ping_results (server_id, pinged_at, pinged_at_epoch, online, latency_ms, players_online, players_max, version, motd, parse_error, players_online_raw, query_data, mods, player_sample, compacted, source, favicon_hash, favicon_changed, error_kind)
+        VALUES (?, COALESCE(?, strftime('%Y-%m-%dT%H:%M:%fZ','now')), CAST(strftime('%s', COALESCE(?, 'now')) AS INTEGER), ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(ping.server_id)
+    .bind(&ping.pinged_at)
+    .bind(&ping.pinged_at)
+    .bind(if ping.online { 1 } else { 0 })
+    .bind(ping.latency_ms)
+    .bind(ping.players_online)
+    .bind(ping.players_max)
+    .bind(&ping.version)
+    .bind(&ping.motd)
+    .bind(ping.parse_error)
+    .bind(ping.players_online_raw)
+    .bind(&ping.query_data)
+    .bind(&ping.mods)
+    .bind(&ping.player_sample)
+    .bind(ping.compacted)
+    .bind(ping.source)
+    .bind(&ping.favicon_hash)
+    .bind(ping.favicon_changed)
+    .bind(ping.error_kind)
+}
+
 impl Database {
     /// Initialize the database connection pool, run migrations, and configure performance settings.
-    pub async fn init(db_url: &str) -> Result<Self, Error> {
-        // 1. Create database file if it doesn't exist
+    /// `wal_autocheckpoint` overrides SQLite's default of 1000 pages on every pooled connection.
+    pub async fn init(
+        db_url: &str,
+        wal_autocheckpoint: Option<u32>,
+        read_pool_size: u32,
+    ) -> Result<Self, DbError> {
+        // 1. Create database file (and its directory, for fresh volumes) if it doesn't exist
         if !Sqlite::database_exists(db_url).await.unwrap_or(false) {
-            println!("Creating database file at: {}", db_url);
+            let path = SqliteConnectOptions::from_str(db_url)?
+                .get_filename()
+                .to_owned();
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty())
+                && !dir.exists()
+            {
+                info!("Creating database directory: {}", dir.display());
+                std::fs::create_dir_all(dir).map_err(Error::Io)?;
+            }
+            info!("Creating database file at: {}", db_url);
             Sqlite::create_database(db_url).await?;
         }
 
         // 2. Connect to the database
-        let pool = SqlitePool::connect(db_url).await?;
+        // sqlx turns foreign keys on by default, but the ON DELETE CASCADEs in the schema
+        // (pings of deleted servers, sessions of deleted admins) depend on it, so say so
+        let mut options = SqliteConnectOptions::from_str(db_url)?.foreign_keys(true);
+        if let Some(pages) = wal_autocheckpoint {
+            // Per-connection setting, so it goes on the connect options rather than a one-off PRAGMA
+            options = options.pragma("wal_autocheckpoint", pages.to_string());
+        }
+        let pool = SqlitePool::connect_with(options.clone()).await?;
 
         // 3. PERFORMANCE: Enable WAL Mode (Write-Ahead Logging)
         // This allows concurrent reads and writes, preventing the UI from freezing
@@ -61,22 +665,35 @@ impl Database {
             .execute(&pool)
             .await?;
 
-        let db = Self { pool };
+        // 4. Read pool, opened once the file is in WAL mode
+        let read_pool = SqlitePoolOptions::new()
+            .max_connections(read_pool_size)
+            .connect_with(options.read_only(true))
+            .await?;
+        let db = Self { pool, read_pool };
 
-        // 4. Ensure schema exists
+        // 5. Ensure schema exists
         db.run_migrations().await?;
 
-        // 5. Seed default data if empty
+        // 6. Seed default data if empty
         db.seed_default_server().await?;
 
         Ok(db)
     }
 
     pub async fn close(&self) {
+        self.read_pool.close().await;
         self.pool.close().await;
     }
 
-    async fn run_migrations(&self) -> Result<(), Error> {
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats {
+            write: PoolUsage::of(&self.pool),
+            read: PoolUsage::of(&self.read_pool),
+        }
+    }
+
+    async fn run_migrations(&self) -> Result<(), DbError> {
         // servers table
         sqlx::query(
             r#"
@@ -112,6 +729,102 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        // Columns added after the initial release
+        self.add_column_if_missing("ping_results", "parse_error", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+
+        self.add_column_if_missing("ping_results", "players_online_raw", "INTEGER")
+            .await?;
+        self.add_column_if_missing("ping_results", "compacted", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing(
+            "ping_results",
+            "source",
+            "TEXT NOT NULL DEFAULT 'scheduled'",
+        )
+        .await?;
+        self.add_column_if_missing("ping_results", "favicon_hash", "TEXT")
+            .await?;
+        self.add_column_if_missing("ping_results", "repeat_count", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("ping_results", "last_repeated_epoch", "INTEGER")
+            .await?;
+        self.add_column_if_missing(
+            "ping_results",
+            "favicon_changed",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+        self.add_column_if_missing("ping_results", "error_kind", "TEXT")
+            .await?;
+        // JSON from the UDP query, kept out of PING_COLUMNS since player lists get big
+        self.add_column_if_missing("ping_results", "query_data", "TEXT")
+            .await?;
+        // Same for Forge mod lists and player samples
+        self.add_column_if_missing("ping_results", "mods", "TEXT")
+            .await?;
+        self.add_column_if_missing("ping_results", "player_sample", "TEXT")
+            .await?;
+        self.add_column_if_missing("servers", "enable_query", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("servers", "query_port", "INTEGER")
+            .await?;
+        self.add_column_if_missing("servers", "protocol_hint", "INTEGER")
+            .await?;
+        self.add_column_if_missing("servers", "muted_until", "INTEGER")
+            .await?;
+        self.add_column_if_missing("servers", "edition", "TEXT NOT NULL DEFAULT 'java'")
+            .await?;
+        self.add_column_if_missing("servers", "color", "TEXT")
+            .await?;
+        // NULL = use the id, so existing and new rows keep creation order without a backfill
+        self.add_column_if_missing("servers", "sort_order", "INTEGER")
+            .await?;
+        self.add_column_if_missing("servers", "is_public", "INTEGER NOT NULL DEFAULT 1")
+            .await?;
+        self.add_column_if_missing("servers", "is_pinned", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("servers", "webhook_url", "TEXT")
+            .await?;
+        self.add_column_if_missing("servers", "modded", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("servers", "check_type", "TEXT NOT NULL DEFAULT 'minecraft'")
+            .await?;
+        self.add_column_if_missing("servers", "confirmed_online", "INTEGER")
+            .await?;
+        self.add_column_if_missing("servers", "player_alert_threshold", "INTEGER")
+            .await?;
+        // Rows from before addresses were normalized on write (see `normalize_address`)
+        sqlx::query(
+            r#"
+            UPDATE servers SET address = lower(rtrim(trim(address), '.'))
+            WHERE address != lower(rtrim(trim(address), '.'))
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Integer copy of pinged_at so range filters don't need string/date parsing
+        self.add_column_if_missing("ping_results", "pinged_at_epoch", "INTEGER")
+            .await?;
+        sqlx::query(
+            r#"
+            UPDATE ping_results
+            SET pinged_at_epoch = CAST(strftime('%s', pinged_at) AS INTEGER)
+            WHERE pinged_at_epoch IS NULL
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_ping_results_server_epoch
+            ON ping_results(server_id, pinged_at_epoch);
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
         // PERFORMANCE: Index for faster graph loading
         // We frequently query by server_id and sort by date.
         sqlx::query(
@@ -123,6 +836,39 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        // server_events table (audit trail of endpoint changes etc.)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS server_events (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                server_id   INTEGER NOT NULL,
+                kind        TEXT NOT NULL,
+                detail      TEXT,
+                created_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+                FOREIGN KEY (server_id) REFERENCES servers(id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // server_tags table (free-form labels for grouping, e.g. a whole network)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS server_tags (
+                server_id   INTEGER NOT NULL,
+                tag         TEXT NOT NULL,
+                PRIMARY KEY (server_id, tag),
+                FOREIGN KEY (server_id) REFERENCES servers(id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_server_tags_tag ON server_tags(tag);")
+            .execute(&self.pool)
+            .await?;
+
         // admin_users table
         sqlx::query(
             r#"
@@ -151,31 +897,63 @@ impl Database {
         )
         .execute(&self.pool)
         .await?;
+        // Admins removed by hand (the sqlite3 shell has foreign keys off) leave their sessions
+        sqlx::query(
+            "DELETE FROM admin_sessions WHERE admin_id NOT IN (SELECT id FROM admin_users)",
+        )
+        .execute(&self.pool)
+        .await?;
 
         Ok(())
     }
 
-    async fn seed_default_server(&self) -> Result<(), Error> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM servers")
-            .fetch_one(&self.pool)
-            .await?;
-
-        let count: i64 = row.try_get("count")?;
+    // SQLite has no `ADD COLUMN IF NOT EXISTS`, so check table_info first
+    async fn add_column_if_missing(
+        &self,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<(), DbError> {
+        let exists = sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .any(|r| r.try_get::<String, _>("name").is_ok_and(|n| n == column));
 
-        if count == 0 {
-            sqlx::query(
-                r#"
-                INSERT INTO servers (name, address, port)
-                VALUES (?, ?, ?)
-                "#,
-            )
-            .bind("Local test server")
-            .bind("localhost")
-            .bind(25565_i64)
+        if !exists {
+            let res = sqlx::query(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, definition
+            ))
             .execute(&self.pool)
-            .await?;
+            .await;
+            match res {
+                Ok(_) => {}
+                // Another instance migrating the same file added it in the meantime
+                Err(Error::Database(e)) if e.message().contains("duplicate column name") => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn seed_default_server(&self) -> Result<(), DbError> {
+        // Single conditional INSERT so concurrent startups sharing the file can't both seed
+        let res = sqlx::query(
+            r#"
+            INSERT INTO servers (name, address, port)
+            SELECT ?, ?, ?
+            WHERE NOT EXISTS (SELECT 1 FROM servers)
+            "#,
+        )
+        .bind("Local test server")
+        .bind("localhost")
+        .bind(25565_i64)
+        .execute(&self.pool)
+        .await?;
 
-            println!("Inserted default server (localhost:25565)");
+        if res.rows_affected() > 0 {
+            info!("Inserted default server (localhost:25565)");
         }
 
         Ok(())
@@ -184,142 +962,1045 @@ impl Database {
     // --- MAINTENANCE ---
 
     /// Deletes ping history older than `days` to keep database size manageable.
-    pub async fn cleanup_old_pings(&self, days: i64) -> Result<u64, Error> {
+    pub async fn cleanup_old_pings(&self, days: i64) -> Result<u64, DbError> {
+        // A collapsed outage stays as long as its latest repeat is within retention
         let res = sqlx::query(
-            r#"DELETE FROM ping_results WHERE pinged_at < date('now', '-' || ? || ' days')"#,
+            r#"DELETE FROM ping_results WHERE COALESCE(last_repeated_epoch, pinged_at_epoch) < ?"#,
         )
-        .bind(days)
+        .bind(now_minus(days as u64 * 24 * 60 * 60))
         .execute(&self.pool)
         .await?;
 
         Ok(res.rows_affected())
     }
-    // --- QUERIES ---
-    pub async fn insert_server(&self, name: &str, address: &str, port: i64) -> Result<i64, Error> {
-        let res = sqlx::query("INSERT INTO servers (name, address, port) VALUES (?, ?, ?)")
-            .bind(name)
-            .bind(address)
-            .bind(port)
+
+    /// Copies the WAL back into the database and truncates the `-wal` file to zero bytes.
+    pub async fn checkpoint_wal(&self) -> Result<(), DbError> {
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE);")
             .execute(&self.pool)
             .await?;
-        Ok(res.last_insert_rowid())
+        Ok(())
     }
 
-    pub async fn delete_server(&self, id: i64) -> Result<u64, Error> {
-        let res = sqlx::query("DELETE FROM servers WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
+    /// Size of the `-wal` file next to the main database, `None` for in-memory databases.
+    pub async fn wal_size_bytes(&self) -> Result<Option<u64>, DbError> {
+        let Some(file) = self.database_file().await? else {
+            return Ok(None);
+        };
+        // A missing -wal file just means nothing has been written since the last checkpoint
+        let size = std::fs::metadata(format!("{}-wal", file))
+            .map(|m| m.len())
+            .unwrap_or(0);
+        Ok(Some(size))
+    }
+
+    /// Size of the database in pages, including changes still waiting in the WAL (the
+    /// file on disk lags behind until the next checkpoint).
+    pub async fn size_bytes(&self) -> Result<u64, DbError> {
+        let pages: i64 = sqlx::query_scalar("PRAGMA page_count;")
+            .fetch_one(&self.read_pool)
             .await?;
-        Ok(res.rows_affected())
+        let page_size: i64 = sqlx::query_scalar("PRAGMA page_size;")
+            .fetch_one(&self.read_pool)
+            .await?;
+        Ok((pages * page_size) as u64)
     }
 
-    pub async fn list_servers(&self) -> Result<Vec<Server>, Error> {
-        sqlx::query_as::<_, Server>(
-            "SELECT id, name, address, port, created_at FROM servers ORDER BY id ASC",
-        )
-        .fetch_all(&self.pool)
-        .await
+    async fn database_file(&self) -> Result<Option<String>, DbError> {
+        let row = sqlx::query("PRAGMA database_list;")
+            .fetch_one(&self.pool)
+            .await?;
+        let file: String = row.try_get("file")?;
+        Ok((!file.is_empty()).then_some(file))
     }
 
-    pub async fn get_server_by_id(&self, id: i64) -> Result<Option<Server>, Error> {
-        sqlx::query_as::<_, Server>(
-            "SELECT id, name, address, port, created_at FROM servers WHERE id = ?",
+    /// Row count of every table, by name.
+    pub async fn table_row_counts(&self) -> Result<Vec<(String, i64)>, DbError> {
+        let names: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
         )
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_all(&self.read_pool)
+        .await?;
+        let mut counts = Vec::with_capacity(names.len());
+        for name in names {
+            // Names come from sqlite_master, not from the request
+            let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM \"{}\"", name))
+                .fetch_one(&self.read_pool)
+                .await?;
+            counts.push((name, count));
+        }
+        Ok(counts)
     }
 
-    pub async fn get_last_ping_for_server(
-        &self,
-        server_id: i64,
-    ) -> Result<Option<PingResult>, Error> {
-        sqlx::query_as::<_, PingResult>(
-            r#"
-            SELECT id, server_id, pinged_at, online, players_online, players_max, version, motd
-            FROM ping_results
-            WHERE server_id = ?
-            ORDER BY pinged_at DESC
-            LIMIT 1
-            "#,
+    /// Ping rows stored since `since_epoch`, and the timestamp of the oldest of them.
+    pub async fn count_pings_since(&self, since_epoch: i64) -> Result<(i64, Option<i64>), DbError> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS count, MIN(pinged_at_epoch) AS oldest FROM ping_results WHERE pinged_at_epoch >= ?",
         )
-        .bind(server_id)
-        .fetch_optional(&self.pool)
+        .bind(since_epoch)
+        .fetch_one(&self.read_pool)
+        .await?;
+        Ok((row.try_get("count")?, row.try_get("oldest")?))
+    }
+
+    /// Writes a consistent snapshot of the whole database to `path` (must not exist yet).
+    /// Safe under WAL, unlike copying the live file.
+    pub async fn backup_to(&self, path: &str) -> Result<(), DbError> {
+        sqlx::query("VACUUM INTO ?")
+            .bind(path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // --- QUERIES ---
+    /// Applies the set fields of `update`. Returns whether the server exists.
+    pub async fn update_server(&self, id: i64, update: &ServerUpdate) -> Result<bool, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let exists = apply_server_update(&mut tx, id, update).await?;
+        tx.commit().await?;
+        Ok(exists)
+    }
+
+    /// Recreates `servers` in one transaction, so a failure leaves none of them behind.
+    /// Returns the new ids in the same order.
+    pub async fn restore_servers(&self, servers: &[RestoredServer]) -> Result<Vec<i64>, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let mut ids = Vec::with_capacity(servers.len());
+        for s in servers {
+            let id = sqlx::query(
+                "INSERT INTO servers (name, address, port, edition, is_pinned) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(&s.name)
+            .bind(normalize_address(&s.address))
+            .bind(s.port)
+            .bind(s.edition)
+            .bind(s.is_pinned)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+            apply_server_update(&mut tx, id, &s.settings).await?;
+            ids.push(id);
+        }
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    pub async fn insert_server(
+        &self,
+        name: &str,
+        address: &str,
+        port: i64,
+        edition: Edition,
+    ) -> Result<i64, DbError> {
+        let res =
+            sqlx::query("INSERT INTO servers (name, address, port, edition) VALUES (?, ?, ?, ?)")
+                .bind(name)
+                .bind(normalize_address(address))
+                .bind(port)
+                .bind(edition)
+                .execute(&self.pool)
+                .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    pub async fn count_servers(&self, include_private: bool) -> Result<i64, DbError> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM servers WHERE ? OR is_public")
+            .bind(include_private)
+            .fetch_one(&self.read_pool)
+            .await?;
+        Ok(row.try_get("count")?)
+    }
+
+    pub async fn delete_server(&self, id: i64) -> Result<u64, DbError> {
+        let res = sqlx::query("DELETE FROM servers WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected())
+    }
+
+    /// Deletes every server in `ids` in one transaction (pings, events and tags cascade).
+    /// Returns the ids that were actually deleted, unknown ones are skipped.
+    pub async fn delete_servers(&self, ids: &[i64]) -> Result<Vec<i64>, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let mut deleted = Vec::new();
+        for id in ids {
+            let res = sqlx::query("DELETE FROM servers WHERE id = ?")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            if res.rows_affected() > 0 {
+                deleted.push(*id);
+            }
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    /// Silences alerts for a server until `until` (Unix time), `None` unmutes.
+    /// Returns false if the server doesn't exist.
+    pub async fn set_muted_until(&self, id: i64, until: Option<i64>) -> Result<bool, DbError> {
+        let res = sqlx::query("UPDATE servers SET muted_until = ? WHERE id = ?")
+            .bind(until)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Flips the pinned flag, returns the new value (`None` for an unknown server).
+    pub async fn toggle_pinned(&self, id: i64) -> Result<Option<bool>, DbError> {
+        sqlx::query_scalar(
+            "UPDATE servers SET is_pinned = NOT is_pinned WHERE id = ? RETURNING is_pinned",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
         .await
+        .map_err(DbError::from)
+    }
+
+    /// Puts the servers in `ids` into that order. They swap positions among themselves,
+    /// so servers not listed keep their place. Returns false (changing nothing) if any
+    /// id doesn't exist. `ids` must not contain duplicates.
+    pub async fn reorder_servers(&self, ids: &[i64]) -> Result<bool, DbError> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut slots = Vec::with_capacity(ids.len());
+        for id in ids {
+            let slot: Option<i64> =
+                sqlx::query_scalar("SELECT COALESCE(sort_order, id) FROM servers WHERE id = ?")
+                    .bind(id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            match slot {
+                Some(slot) => slots.push(slot),
+                None => return Ok(false),
+            }
+        }
+        slots.sort_unstable();
+
+        for (id, slot) in ids.iter().zip(slots) {
+            sqlx::query("UPDATE servers SET sort_order = ? WHERE id = ?")
+                .bind(slot)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Moves a server to a new endpoint and records the old/new pair as a `migrated` event.
+    /// Returns the server as it was before the move, or `None` if it doesn't exist.
+    pub async fn migrate_server(
+        &self,
+        id: i64,
+        address: &str,
+        port: i64,
+    ) -> Result<Option<Server>, DbError> {
+        let address = normalize_address(address);
+        let mut tx = self.pool.begin().await?;
+
+        let old = sqlx::query_as::<_, Server>(&format!(
+            "SELECT {SERVER_COLUMNS} FROM servers WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(old) = old else {
+            return Ok(None);
+        };
+
+        sqlx::query("UPDATE servers SET address = ?, port = ? WHERE id = ?")
+            .bind(&address)
+            .bind(port)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        let detail = format!("{}:{} -> {}:{}", old.address, old.port, address, port);
+        sqlx::query(
+            "INSERT INTO server_events (server_id, kind, detail) VALUES (?, 'migrated', ?)",
+        )
+        .bind(id)
+        .bind(detail)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(old))
+    }
+
+    /// Case-insensitive substring match on name or address, at most `limit` results.
+    pub async fn search_servers(
+        &self,
+        q: &str,
+        limit: i64,
+        include_private: bool,
+    ) -> Result<Vec<Server>, DbError> {
+        let pattern = format!("%{}%", escape_like(q));
+        sqlx::query_as::<_, Server>(&format!(
+            r#"
+            SELECT {SERVER_COLUMNS} FROM servers
+            WHERE (name LIKE ?1 ESCAPE '\' OR address LIKE ?1 ESCAPE '\') AND (?3 OR is_public)
+            ORDER BY name COLLATE NOCASE ASC, id ASC
+            LIMIT ?2
+            "#
+        ))
+        .bind(pattern)
+        .bind(limit)
+        .bind(include_private)
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    pub async fn list_servers_by_tag(
+        &self,
+        tag: &str,
+        include_private: bool,
+    ) -> Result<Vec<Server>, DbError> {
+        sqlx::query_as::<_, Server>(&format!(
+            r#"
+            SELECT {SERVER_COLUMNS} FROM servers
+            WHERE id IN (SELECT server_id FROM server_tags WHERE tag = ?)
+              AND (? OR is_public)
+            ORDER BY is_pinned DESC, COALESCE(sort_order, id) ASC, id ASC
+            "#
+        ))
+        .bind(normalize_tag(tag))
+        .bind(include_private)
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// Total and online ping counts across the servers tagged `tag` in the last `seconds_ago`.
+    pub async fn get_group_ping_counts(
+        &self,
+        tag: &str,
+        seconds_ago: u64,
+        include_private: bool,
+    ) -> Result<(i64, i64), DbError> {
+        let row = sqlx::query(
+            r#"
+            SELECT COALESCE(SUM(1 + repeat_count), 0) AS samples, COALESCE(SUM(online), 0) AS online
+            FROM ping_results
+            WHERE server_id IN (
+                    SELECT t.server_id FROM server_tags t JOIN servers s ON s.id = t.server_id
+                    WHERE t.tag = ? AND (? OR s.is_public)
+                  )
+              AND pinged_at_epoch >= ?
+            "#,
+        )
+        .bind(normalize_tag(tag))
+        .bind(include_private)
+        .bind(now_minus(seconds_ago))
+        .fetch_one(&self.read_pool)
+        .await?;
+        Ok((row.try_get("samples")?, row.try_get("online")?))
+    }
+
+    /// Number of servers per version, taken from each server's latest ping. Servers that are
+    /// offline right now (no version in that ping) aren't counted. Most common first.
+    pub async fn count_current_versions(
+        &self,
+        include_private: bool,
+    ) -> Result<Vec<(String, i64)>, DbError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT version, COUNT(*) AS servers
+            FROM (
+                SELECT (
+                    SELECT version FROM ping_results
+                    WHERE server_id = servers.id
+                    ORDER BY pinged_at_epoch DESC, id DESC
+                    LIMIT 1
+                ) AS version
+                FROM servers
+                WHERE ? OR is_public
+            )
+            WHERE version IS NOT NULL
+            GROUP BY version
+            ORDER BY servers DESC, version ASC
+            "#,
+        )
+        .bind(include_private)
+        .fetch_all(&self.read_pool)
+        .await?;
+        rows.iter()
+            .map(|r| Ok((r.try_get("version")?, r.try_get("servers")?)))
+            .collect()
+    }
+
+    pub async fn insert_server_event(
+        &self,
+        server_id: i64,
+        kind: &str,
+        detail: &str,
+    ) -> Result<(), DbError> {
+        sqlx::query("INSERT INTO server_events (server_id, kind, detail) VALUES (?, ?, ?)")
+            .bind(server_id)
+            .bind(kind)
+            .bind(detail)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether `kind` was recorded for the server within the last `secs` seconds.
+    pub async fn has_recent_server_event(
+        &self,
+        server_id: i64,
+        kind: &str,
+        secs: i64,
+    ) -> Result<bool, DbError> {
+        let row = sqlx::query(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM server_events
+                WHERE server_id = ? AND kind = ?
+                  AND created_at >= strftime('%Y-%m-%dT%H:%M:%fZ', 'now', '-' || ? || ' seconds')
+            ) AS recent
+            "#,
+        )
+        .bind(server_id)
+        .bind(kind)
+        .bind(secs)
+        .fetch_one(&self.read_pool)
+        .await?;
+        Ok(row.try_get("recent")?)
+    }
+
+    pub async fn list_server_events(&self, server_id: i64) -> Result<Vec<ServerEvent>, DbError> {
+        sqlx::query_as::<_, ServerEvent>(
+            r#"
+            SELECT id, server_id, kind, detail, created_at
+            FROM server_events
+            WHERE server_id = ?
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(server_id)
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    pub async fn list_servers(&self) -> Result<Vec<Server>, DbError> {
+        sqlx::query_as::<_, Server>(&format!(
+            "SELECT {SERVER_COLUMNS} FROM servers ORDER BY is_pinned DESC, COALESCE(sort_order, id) ASC, id ASC"
+        ))
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    pub async fn get_server_by_id(&self, id: i64) -> Result<Option<Server>, DbError> {
+        sqlx::query_as::<_, Server>(&format!(
+            "SELECT {SERVER_COLUMNS} FROM servers WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    pub async fn find_server_by_endpoint(
+        &self,
+        address: &str,
+        port: i64,
+    ) -> Result<Option<Server>, DbError> {
+        sqlx::query_as::<_, Server>(&format!(
+            "SELECT {SERVER_COLUMNS} FROM servers WHERE address = ? AND port = ? LIMIT 1"
+        ))
+        .bind(normalize_address(address))
+        .bind(port)
+        .fetch_optional(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// Servers whose latest ping is offline, with when the current outage started.
+    pub async fn list_offline_servers(&self) -> Result<Vec<OfflineServer>, DbError> {
+        sqlx::query_as::<_, OfflineServer>(
+            r#"
+            SELECT s.id, s.name, s.address, s.port, s.is_public,
+                   (SELECT MIN(p.pinged_at_epoch)
+                    FROM ping_results p
+                    WHERE p.server_id = s.id
+                      AND p.pinged_at_epoch > COALESCE(
+                          (SELECT MAX(o.pinged_at_epoch) FROM ping_results o
+                           WHERE o.server_id = s.id AND o.online = 1), 0)
+                   ) AS down_since_epoch
+            FROM servers s
+            JOIN ping_results lp ON lp.id = (
+                SELECT id FROM ping_results
+                WHERE server_id = s.id
+                ORDER BY pinged_at_epoch DESC, id DESC
+                LIMIT 1
+            )
+            WHERE lp.online = 0
+            ORDER BY down_since_epoch ASC
+            "#,
+        )
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// Most recent stored UDP query result as `(pinged_at, json)`.
+    pub async fn get_last_query_data(
+        &self,
+        server_id: i64,
+    ) -> Result<Option<(String, String)>, DbError> {
+        sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT pinged_at, query_data
+            FROM ping_results
+            WHERE server_id = ? AND query_data IS NOT NULL
+            ORDER BY pinged_at_epoch DESC, id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(server_id)
+        .fetch_optional(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// Mod list from the most recent ping that reported one, as `(pinged_at, json)`.
+    pub async fn get_last_mods(&self, server_id: i64) -> Result<Option<(String, String)>, DbError> {
+        sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT pinged_at, mods
+            FROM ping_results
+            WHERE server_id = ? AND mods IS NOT NULL
+            ORDER BY pinged_at_epoch DESC, id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(server_id)
+        .fetch_optional(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// Player sample of the most recent ping that was answered, as `(pinged_at, json)`.
+    pub async fn get_last_player_sample(
+        &self,
+        server_id: i64,
+    ) -> Result<Option<(String, String)>, DbError> {
+        sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT pinged_at, player_sample
+            FROM ping_results
+            WHERE server_id = ? AND player_sample IS NOT NULL
+            ORDER BY pinged_at_epoch DESC, id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(server_id)
+        .fetch_optional(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// Favicon hash from the most recent ping that recorded one (`""` = no favicon).
+    pub async fn get_last_favicon_hash(&self, server_id: i64) -> Result<Option<String>, DbError> {
+        sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT favicon_hash
+            FROM ping_results
+            WHERE server_id = ? AND favicon_hash IS NOT NULL
+            ORDER BY pinged_at_epoch DESC, id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(server_id)
+        .fetch_optional(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    pub async fn get_last_ping_for_server(
+        &self,
+        server_id: i64,
+    ) -> Result<Option<PingResult>, DbError> {
+        let row = sqlx::query_as::<_, PingResult>(&format!(
+            r#"
+            SELECT {PING_COLUMNS}
+            FROM ping_results
+            WHERE server_id = ?
+            ORDER BY pinged_at_epoch DESC, id DESC
+            LIMIT 1
+            "#
+        ))
+        .bind(server_id)
+        .fetch_optional(&self.read_pool)
+        .await?;
+        // A collapsed row was last seen at its latest repeat
+        Ok(row.and_then(|p| expand_repeats(vec![p]).pop()))
+    }
+
+    /// How many of the newest pings in a row were `online` (collapsed repeats included),
+    /// looking at no more than `limit` rows.
+    pub async fn count_trailing_pings(
+        &self,
+        server_id: i64,
+        online: bool,
+        limit: i64,
+    ) -> Result<i64, DbError> {
+        let rows = sqlx::query_as::<_, (bool, i64)>(
+            r#"
+            SELECT online, repeat_count
+            FROM ping_results
+            WHERE server_id = ?
+            ORDER BY pinged_at_epoch DESC, id DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(server_id)
+        .bind(limit)
+        .fetch_all(&self.read_pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .take_while(|(o, _)| *o == online)
+            .map(|(_, repeats)| 1 + repeats)
+            .sum())
+    }
+
+    pub async fn set_confirmed_online(&self, server_id: i64, online: bool) -> Result<(), DbError> {
+        sqlx::query("UPDATE servers SET confirmed_online = ? WHERE id = ?")
+            .bind(online)
+            .bind(server_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Time of the newest ping of any server (collapsed repeats included).
+    pub async fn latest_ping_epoch(&self) -> Result<Option<i64>, DbError> {
+        // One index lookup per server instead of scanning every ping
+        sqlx::query_scalar(
+            r#"
+            SELECT MAX((
+                SELECT CASE WHEN repeat_count > 0 THEN last_repeated_epoch ELSE pinged_at_epoch END
+                FROM ping_results
+                WHERE server_id = servers.id
+                ORDER BY pinged_at_epoch DESC
+                LIMIT 1
+            ))
+            FROM servers
+            "#,
+        )
+        .fetch_one(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// A collapsed row comes back as its latest repeat.
+    pub async fn get_ping_by_id(&self, id: i64) -> Result<Option<PingResult>, DbError> {
+        let row = sqlx::query_as::<_, PingResult>(&format!(
+            "SELECT {PING_COLUMNS} FROM ping_results WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.read_pool)
+        .await?;
+        Ok(row.and_then(|p| expand_repeats(vec![p]).pop()))
+    }
+
+    /// The last ping at or before `epoch`, `None` if the history starts later.
+    pub async fn get_ping_at(
+        &self,
+        server_id: i64,
+        epoch: i64,
+    ) -> Result<Option<PingResult>, DbError> {
+        let row = sqlx::query_as::<_, PingResult>(&format!(
+            r#"
+            SELECT {PING_COLUMNS}
+            FROM ping_results
+            WHERE server_id = ? AND pinged_at_epoch <= ?
+            ORDER BY pinged_at_epoch DESC, id DESC
+            LIMIT 1
+            "#
+        ))
+        .bind(server_id)
+        .bind(epoch)
+        .fetch_optional(&self.read_pool)
+        .await?;
+        // Inside a collapsed outage, answer with the repeat just before `epoch`
+        Ok(row.and_then(|p| {
+            expand_repeats(vec![p])
+                .into_iter()
+                .rfind(|p| p.pinged_at_epoch <= epoch)
+        }))
     }
 
+    /// Pings of a server, oldest first, optionally limited to the last `seconds_ago`.
+    /// With `since_id`, only rows after that id are returned, except that the `since_id` row
+    /// itself comes again when it is a collapsed outage: it keeps its id while repeats are
+    /// folded into it, so a client replaces the points it has for that id.
     pub async fn get_pings_subset(
         &self,
         server_id: i64,
         since_id: Option<i64>,
         seconds_ago: Option<u64>,
-    ) -> Result<Vec<PingResult>, Error> {
-        let mut sql = String::from(
+    ) -> Result<Vec<PingResult>, DbError> {
+        let mut sql = format!(
             r#"
-            SELECT id, server_id, pinged_at, online, players_online, players_max, version, motd
+            SELECT {PING_COLUMNS}
             FROM ping_results
             WHERE server_id = ?
-            "#,
+            "#
         );
 
         // If we only want new data (Incremental update)
-        if let Some(_) = since_id {
-            sql.push_str(" AND id > ?");
+        if since_id.is_some() {
+            sql.push_str(" AND (id > ? OR (id = ? AND repeat_count > 0))");
         }
 
         // If we are fetching a specific range (Day/Week/Month)
-        if let Some(sec) = seconds_ago {
-            // SQLite specific date math
+        // Collapsed rows that started earlier but repeat into the window count too
+        let cutoff = seconds_ago.map(now_minus);
+        if let Some(cutoff) = cutoff {
             sql.push_str(&format!(
-                " AND pinged_at >= datetime('now', '-{} seconds')",
-                sec
+                " AND (pinged_at_epoch >= {cutoff} OR last_repeated_epoch >= {cutoff})"
             ));
         }
 
-        sql.push_str(" ORDER BY pinged_at ASC"); // We want oldest to newest for the graph
+        sql.push_str(" ORDER BY pinged_at_epoch ASC"); // We want oldest to newest for the graph
 
         let mut query = sqlx::query_as::<_, PingResult>(&sql).bind(server_id);
 
         if let Some(sid) = since_id {
-            query = query.bind(sid);
+            query = query.bind(sid).bind(sid);
         }
 
-        query.fetch_all(&self.pool).await
+        let mut pings = expand_repeats(query.fetch_all(&self.read_pool).await?);
+        if let Some(cutoff) = cutoff {
+            pings.retain(|p| p.pinged_at_epoch >= cutoff);
+        }
+        Ok(pings)
     }
 
-    pub async fn insert_ping_result(
+    /// Sends every ping of a server into `tx`, oldest first, reading through a cursor so
+    /// any amount of history goes through with flat memory. A full channel pauses the
+    /// cursor; a dropped receiver ends it.
+    pub async fn send_pings(
         &self,
         server_id: i64,
-        online: bool,
-        latency_ms: Option<i64>,
-        players_online: Option<i64>,
-        players_max: Option<i64>,
-        version: Option<&str>,
-        motd: Option<&str>,
-    ) -> Result<i64, Error> {
-        let res = sqlx::query(
+        tx: mpsc::Sender<PingResult>,
+    ) -> Result<(), DbError> {
+        let sql = format!(
+            r#"
+            SELECT {PING_COLUMNS}
+            FROM ping_results
+            WHERE server_id = ?
+            ORDER BY pinged_at_epoch ASC, id ASC
+            "#
+        );
+        let mut rows = sqlx::query_as::<_, PingResult>(&sql)
+            .bind(server_id)
+            .fetch(&self.read_pool);
+        while let Some(row) = rows.try_next().await? {
+            for ping in expand_repeats(vec![row]) {
+                if tx.send(ping).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// All pings of a server older than `before_epoch`, oldest first.
+    pub async fn get_pings_before(
+        &self,
+        server_id: i64,
+        before_epoch: i64,
+    ) -> Result<Vec<PingResult>, DbError> {
+        let rows = sqlx::query_as::<_, PingResult>(&format!(
+            r#"
+            SELECT {PING_COLUMNS}
+            FROM ping_results
+            WHERE server_id = ? AND pinged_at_epoch < ?
+            ORDER BY pinged_at_epoch ASC
+            "#
+        ))
+        .bind(server_id)
+        .bind(before_epoch)
+        .fetch_all(&self.read_pool)
+        .await?;
+        // Repeats past `before_epoch` are kept: the row holding them is deleted with the rest
+        Ok(expand_repeats(rows))
+    }
+
+    /// Swaps every ping older than `before_epoch` for `points` in one transaction and logs a
+    /// `compacted` event. Returns how many rows were deleted.
+    pub async fn compact_pings(
+        &self,
+        server_id: i64,
+        before_epoch: i64,
+        points: &[NewPing],
+    ) -> Result<u64, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let deleted =
+            sqlx::query("DELETE FROM ping_results WHERE server_id = ? AND pinged_at_epoch < ?")
+                .bind(server_id)
+                .bind(before_epoch)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        for p in points {
+            ping_insert_query(p).execute(&mut *tx).await?;
+        }
+
+        let detail = format!("{} pings -> {} points", deleted, points.len());
+        sqlx::query(
+            "INSERT INTO server_events (server_id, kind, detail) VALUES (?, 'compacted', ?)",
+        )
+        .bind(server_id)
+        .bind(detail)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    /// Player counts of online pings within the window, sorted ascending.
+    pub async fn get_online_player_counts(
+        &self,
+        server_id: i64,
+        seconds_ago: u64,
+    ) -> Result<Vec<i64>, DbError> {
+        let rows = sqlx::query(
             r#"
-            INSERT INTO ping_results (server_id, online, latency_ms, players_online, players_max, version, motd)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            SELECT players_online
+            FROM ping_results
+            WHERE server_id = ? AND online = 1 AND players_online IS NOT NULL
+              AND pinged_at_epoch >= ?
+            ORDER BY players_online ASC
             "#,
         )
-            .bind(server_id)
-            .bind(if online { 1 } else { 0 })
-            .bind(latency_ms)
-            .bind(players_online)
-            .bind(players_max)
-            .bind(version)
-            .bind(motd)
-            .execute(&self.pool)
-            .await?;
+        .bind(server_id)
+        .bind(now_minus(seconds_ago))
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|r| r.try_get("players_online"))
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// Groups pings since `start_epoch` into `bucket_secs` wide buckets, counting online ones.
+    /// Buckets without any pings are simply absent. Collapsed rows count as `1 + repeat_count`
+    /// pings, spread the same way `expand_repeats` does.
+    pub async fn get_bucket_counts(
+        &self,
+        server_id: i64,
+        start_epoch: i64,
+        bucket_secs: i64,
+    ) -> Result<Vec<BucketCounts>, DbError> {
+        sqlx::query_as::<_, BucketCounts>(
+            r#"
+            WITH RECURSIVE repeats(id, k) AS (
+                SELECT id, 1 FROM ping_results
+                WHERE server_id = ?3 AND repeat_count > 0 AND last_repeated_epoch >= ?1
+                UNION ALL
+                SELECT r.id, r.k + 1 FROM repeats r JOIN ping_results p ON p.id = r.id
+                WHERE r.k < p.repeat_count
+            ),
+            expanded AS (
+                SELECT pinged_at_epoch, online, players_online FROM ping_results
+                WHERE server_id = ?3 AND pinged_at_epoch >= ?1
+                UNION ALL
+                SELECT p.pinged_at_epoch
+                           + (p.last_repeated_epoch - p.pinged_at_epoch) * r.k / p.repeat_count,
+                       p.online, p.players_online
+                FROM repeats r JOIN ping_results p ON p.id = r.id
+            )
+            SELECT (pinged_at_epoch - ?1) / ?2 AS bucket,
+                   COUNT(*) AS samples,
+                   SUM(online) AS online,
+                   AVG(CASE WHEN online THEN players_online END) AS avg_players
+            FROM expanded
+            WHERE pinged_at_epoch >= ?1
+            GROUP BY bucket
+            ORDER BY bucket ASC
+            "#,
+        )
+        .bind(start_epoch)
+        .bind(bucket_secs)
+        .bind(server_id)
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// Total players per `bucket_secs` wide bucket since `start_epoch`, taking each server's
+    /// latest ping in the bucket (offline counts as 0). Empty buckets are absent. Collapsed
+    /// outages are expanded like in `get_bucket_counts`.
+    pub async fn get_network_player_buckets(
+        &self,
+        start_epoch: i64,
+        bucket_secs: i64,
+        include_private: bool,
+    ) -> Result<Vec<NetworkBucket>, DbError> {
+        sqlx::query_as::<_, NetworkBucket>(
+            r#"
+            WITH RECURSIVE repeats(id, k) AS (
+                SELECT id, 1 FROM ping_results
+                WHERE repeat_count > 0 AND last_repeated_epoch >= ?1
+                UNION ALL
+                SELECT r.id, r.k + 1 FROM repeats r JOIN ping_results p ON p.id = r.id
+                WHERE r.k < p.repeat_count
+            ),
+            expanded AS (
+                SELECT id, server_id, pinged_at_epoch, online, players_online FROM ping_results
+                WHERE pinged_at_epoch >= ?1
+                UNION ALL
+                SELECT p.id, p.server_id,
+                       p.pinged_at_epoch
+                           + (p.last_repeated_epoch - p.pinged_at_epoch) * r.k / p.repeat_count,
+                       p.online, p.players_online
+                FROM repeats r JOIN ping_results p ON p.id = r.id
+            )
+            SELECT bucket, SUM(players) AS players, COUNT(*) AS servers
+            FROM (
+                SELECT (pinged_at_epoch - ?1) / ?2 AS bucket,
+                       CASE WHEN online THEN COALESCE(players_online, 0) ELSE 0 END AS players,
+                       ROW_NUMBER() OVER (
+                           PARTITION BY server_id, (pinged_at_epoch - ?1) / ?2
+                           ORDER BY pinged_at_epoch DESC, id DESC
+                       ) AS nth
+                FROM expanded
+                WHERE pinged_at_epoch >= ?1
+                  AND server_id IN (SELECT id FROM servers WHERE ?3 OR is_public)
+            )
+            WHERE nth = 1
+            GROUP BY bucket
+            ORDER BY bucket ASC
+            "#,
+        )
+        .bind(start_epoch)
+        .bind(bucket_secs)
+        .bind(include_private)
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// Average player count of online pings per weekday/hour slot over the last `seconds_ago`.
+    /// Times are shifted by `offset_secs` first so slots can follow a local timezone.
+    pub async fn get_hourly_activity(
+        &self,
+        server_id: i64,
+        seconds_ago: u64,
+        offset_secs: i64,
+    ) -> Result<Vec<HourlyActivity>, DbError> {
+        sqlx::query_as::<_, HourlyActivity>(
+            r#"
+            SELECT CAST(strftime('%w', pinged_at_epoch + ?, 'unixepoch') AS INTEGER) AS weekday,
+                   CAST(strftime('%H', pinged_at_epoch + ?, 'unixepoch') AS INTEGER) AS hour,
+                   COUNT(*) AS samples,
+                   AVG(players_online) AS avg_players
+            FROM ping_results
+            WHERE server_id = ? AND pinged_at_epoch >= ?
+              AND online = 1 AND players_online IS NOT NULL
+            GROUP BY weekday, hour
+            "#,
+        )
+        .bind(offset_secs)
+        .bind(offset_secs)
+        .bind(server_id)
+        .bind(now_minus(seconds_ago))
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// `(pinged_at, motd)` of the online pings in the last `seconds_ago`, oldest first.
+    pub async fn get_motds(
+        &self,
+        server_id: i64,
+        seconds_ago: u64,
+    ) -> Result<Vec<(String, String)>, DbError> {
+        sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT pinged_at, motd
+            FROM ping_results
+            WHERE server_id = ? AND pinged_at_epoch >= ?
+              AND online = 1 AND motd IS NOT NULL
+            ORDER BY pinged_at_epoch ASC, id ASC
+            "#,
+        )
+        .bind(server_id)
+        .bind(now_minus(seconds_ago))
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// Folds a new offline scheduled ping into the server's latest row if that one is an
+    /// offline scheduled ping that failed the same way. Returns the id of that row, or `None`
+    /// (nothing written) otherwise.
+    pub async fn collapse_repeated_offline(
+        &self,
+        server_id: i64,
+        error_kind: Option<PingErrorKind>,
+    ) -> Result<Option<i64>, DbError> {
+        sqlx::query_scalar(
+            r#"
+            UPDATE ping_results
+            SET repeat_count = repeat_count + 1,
+                last_repeated_epoch = CAST(strftime('%s', 'now') AS INTEGER)
+            WHERE id = (
+                SELECT id FROM ping_results
+                WHERE server_id = ?
+                ORDER BY pinged_at_epoch DESC, id DESC
+                LIMIT 1
+            )
+              AND online = 0 AND source = 'scheduled' AND compacted = 0 AND error_kind IS ?
+            RETURNING id
+            "#,
+        )
+        .bind(server_id)
+        .bind(error_kind)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DbError::from)
+    }
+
+    pub async fn insert_ping_result(&self, ping: &NewPing) -> Result<i64, DbError> {
+        let res = ping_insert_query(ping).execute(&self.pool).await?;
         Ok(res.last_insert_rowid())
     }
 
+    /// Inserts many pings in a single transaction, all or nothing.
+    pub async fn insert_ping_results_batch(&self, pings: &[NewPing]) -> Result<u64, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = 0;
+        for ping in pings {
+            inserted += ping_insert_query(ping)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
     /*
     pub async fn list_ping_results_for_server(
         &self,
         server_id: i64,
-    ) -> Result<Vec<PingResult>, Error> {
+    ) -> Result<Vec<PingResult>, DbError> {
         // Limit history to last 144 points to prevent frontend lag if data grows huge
         sqlx::query_as::<_, PingResult>(
             r#"
@@ -331,48 +2012,67 @@ impl Database {
             "#,
         )
         .bind(server_id)
-        .fetch_all(&self.pool)
+        .fetch_all(&self.read_pool)
         .await
+        .map_err(DbError::from)
     }
     */
 
     // --- AUTH ---
 
+    /// Creates the admin if the username is free. Returns whether a row was inserted;
+    /// losing a race against another instance is not an error.
     pub async fn ensure_admin_user(
         &self,
         username: &str,
         password_hash: &str,
-    ) -> Result<(), Error> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM admin_users WHERE username = ?")
-            .bind(username)
-            .fetch_one(&self.pool)
-            .await?;
+    ) -> Result<bool, DbError> {
+        let res = sqlx::query(
+            "INSERT OR IGNORE INTO admin_users (username, password_hash) VALUES (?, ?)",
+        )
+        .bind(username)
+        .bind(password_hash)
+        .execute(&self.pool)
+        .await?;
 
-        if row.try_get::<i64, _>("count")? == 0 {
-            sqlx::query("INSERT INTO admin_users (username, password_hash) VALUES (?, ?)")
-                .bind(username)
-                .bind(password_hash)
-                .execute(&self.pool)
-                .await?;
-            println!("Created default admin user '{}'", username);
+        let created = res.rows_affected() > 0;
+        if created {
+            info!("Created default admin user '{}'", username);
         }
-        Ok(())
+        Ok(created)
     }
 
-    pub async fn get_admin_by_username(&self, username: &str) -> Result<Option<AdminUser>, Error> {
+    pub async fn get_admin_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<AdminUser>, DbError> {
         sqlx::query_as::<_, AdminUser>(
             "SELECT id, username, password_hash, created_at FROM admin_users WHERE username = ?",
         )
         .bind(username)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.read_pool)
         .await
+        .map_err(DbError::from)
+    }
+
+    pub async fn update_admin_password_hash(
+        &self,
+        admin_id: i64,
+        password_hash: &str,
+    ) -> Result<(), DbError> {
+        sqlx::query("UPDATE admin_users SET password_hash = ? WHERE id = ?")
+            .bind(password_hash)
+            .bind(admin_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
     pub async fn create_admin_session(
         &self,
         admin_id: i64,
         session_token: &str,
-    ) -> Result<(), Error> {
+    ) -> Result<(), DbError> {
         sqlx::query("INSERT INTO admin_sessions (admin_id, session_token) VALUES (?, ?)")
             .bind(admin_id)
             .bind(session_token)
@@ -381,24 +2081,63 @@ impl Database {
         Ok(())
     }
 
+    /// Admin of a session that's younger than `ttl_secs`.
     pub async fn get_admin_by_session_token(
         &self,
         session_token: &str,
-    ) -> Result<Option<AdminUser>, Error> {
+        ttl_secs: i64,
+    ) -> Result<Option<AdminUser>, DbError> {
         sqlx::query_as::<_, AdminUser>(
             r#"
             SELECT u.id, u.username, u.password_hash, u.created_at
             FROM admin_sessions s
             JOIN admin_users u ON s.admin_id = u.id
             WHERE s.session_token = ?
+              AND s.created_at >= strftime('%Y-%m-%dT%H:%M:%fZ', 'now', '-' || ? || ' seconds')
             "#,
         )
         .bind(session_token)
-        .fetch_optional(&self.pool)
+        .bind(ttl_secs)
+        .fetch_optional(&self.read_pool)
         .await
+        .map_err(DbError::from)
+    }
+
+    /// Deletes all but the `keep` newest sessions of the admin, returns how many.
+    pub async fn prune_oldest_sessions(&self, admin_id: i64, keep: i64) -> Result<u64, DbError> {
+        let res = sqlx::query(
+            r#"
+            DELETE FROM admin_sessions
+            WHERE admin_id = ?1 AND id NOT IN (
+                SELECT id FROM admin_sessions
+                WHERE admin_id = ?1
+                ORDER BY created_at DESC, id DESC
+                LIMIT ?2
+            )
+            "#,
+        )
+        .bind(admin_id)
+        .bind(keep)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
     }
 
-    pub async fn delete_session(&self, session_token: &str) -> Result<(), Error> {
+    /// Deletes sessions older than `ttl_secs`, returns how many.
+    pub async fn cleanup_expired_sessions(&self, ttl_secs: i64) -> Result<u64, DbError> {
+        let res = sqlx::query(
+            r#"
+            DELETE FROM admin_sessions
+            WHERE created_at < strftime('%Y-%m-%dT%H:%M:%fZ', 'now', '-' || ? || ' seconds')
+            "#,
+        )
+        .bind(ttl_secs)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    pub async fn delete_session(&self, session_token: &str) -> Result<(), DbError> {
         sqlx::query("DELETE FROM admin_sessions WHERE session_token = ?")
             .bind(session_token)
             .execute(&self.pool)
@@ -406,3 +2145,305 @@ impl Database {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Deref;
+    use std::path::PathBuf;
+
+    /// A migrated database in a temp file, deleted on drop. Both pools need to see the
+    /// same data, which rules out `sqlite::memory:`.
+    struct TestDb {
+        db: Database,
+        path: PathBuf,
+    }
+
+    impl TestDb {
+        async fn new() -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("web-server-test-{:x}.db", rand::random::<u64>()));
+            let db = Database::init(&format!("sqlite://{}", path.display()), None, 1)
+                .await
+                .unwrap();
+            Self { db, path }
+        }
+    }
+
+    impl Deref for TestDb {
+        type Target = Database;
+
+        fn deref(&self) -> &Database {
+            &self.db
+        }
+    }
+
+    impl Drop for TestDb {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{}", self.path.display(), suffix));
+            }
+        }
+    }
+
+    // The server seeded into every fresh database
+    const SEEDED_SERVER: i64 = 1;
+
+    fn ping_at(secs_ago: i64, online: bool) -> NewPing {
+        let at = chrono::Utc::now() - chrono::Duration::seconds(secs_ago);
+        NewPing {
+            server_id: SEEDED_SERVER,
+            pinged_at: Some(at.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()),
+            online,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn normalize_address_folds_case_whitespace_and_trailing_dot() {
+        assert_eq!(normalize_address("Example.COM."), "example.com");
+        assert_eq!(normalize_address("  mc.example.com  "), "mc.example.com");
+        assert_eq!(normalize_address("example.com"), "example.com");
+        assert_eq!(normalize_address("2001:DB8::1"), "2001:db8::1");
+    }
+
+    #[test]
+    fn normalize_address_leaves_non_ascii_alone() {
+        // SQLite's lower() only folds ASCII, older rows were normalized with it
+        assert_eq!(normalize_address("ÄBC.example"), "Äbc.example");
+    }
+
+    #[tokio::test]
+    async fn incremental_poll_stays_inside_the_window() {
+        let db = TestDb::new().await;
+        let before = db.insert_ping_result(&ping_at(60, true)).await.unwrap();
+        let stale = db
+            .insert_ping_result(&ping_at(2 * 86400, true))
+            .await
+            .unwrap();
+        let fresh = db.insert_ping_result(&ping_at(30, true)).await.unwrap();
+
+        let ids: Vec<i64> = db
+            .get_pings_subset(SEEDED_SERVER, Some(before), Some(86400))
+            .await
+            .unwrap()
+            .iter()
+            .map(|p| p.id)
+            .collect();
+
+        // `stale` is newer than `before` by id, but two days old
+        assert!(!ids.contains(&stale));
+        assert_eq!(ids, vec![fresh]);
+    }
+
+    // Folds `repeats` more pings into `id`, `every` seconds apart, like the scheduler would
+    async fn collapse(db: &Database, id: i64, repeats: i64, every: i64) -> i64 {
+        sqlx::query(
+            "UPDATE ping_results SET repeat_count = ?, last_repeated_epoch = pinged_at_epoch + ? WHERE id = ?",
+        )
+        .bind(repeats)
+        .bind(repeats * every)
+        .bind(id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        sqlx::query_scalar("SELECT pinged_at_epoch FROM ping_results WHERE id = ?")
+            .bind(id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn bucket_counts_spread_collapsed_repeats() {
+        let db = TestDb::new().await;
+        let id = db.insert_ping_result(&ping_at(3000, false)).await.unwrap();
+        let first = collapse(&db, id, 5, 500).await;
+
+        let counts: Vec<(i64, i64, i64)> = db
+            .get_bucket_counts(SEEDED_SERVER, first, 600)
+            .await
+            .unwrap()
+            .iter()
+            .map(|b| (b.bucket, b.samples, b.online))
+            .collect();
+
+        // Pings at +0, +500, ..., +2500
+        assert_eq!(
+            counts,
+            vec![(0, 2, 0), (1, 1, 0), (2, 1, 0), (3, 1, 0), (4, 1, 0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn network_buckets_spread_collapsed_repeats() {
+        let db = TestDb::new().await;
+        let id = db.insert_ping_result(&ping_at(3000, false)).await.unwrap();
+        let first = collapse(&db, id, 5, 500).await;
+
+        let servers: Vec<(i64, i64)> = db
+            .get_network_player_buckets(first, 600, true)
+            .await
+            .unwrap()
+            .iter()
+            .map(|b| (b.bucket, b.servers))
+            .collect();
+
+        // The server is counted in every bucket the outage spans, not just the first
+        assert_eq!(servers, vec![(0, 1), (1, 1), (2, 1), (3, 1), (4, 1)]);
+    }
+
+    #[tokio::test]
+    async fn incremental_poll_resends_a_grown_collapsed_row() {
+        let db = TestDb::new().await;
+        let online = db.insert_ping_result(&ping_at(600, true)).await.unwrap();
+        let outage = db.insert_ping_result(&ping_at(300, false)).await.unwrap();
+        let day = Some(86400);
+
+        // Not collapsed yet: nothing after it
+        let pings = db
+            .get_pings_subset(SEEDED_SERVER, Some(outage), day)
+            .await
+            .unwrap();
+        assert!(pings.is_empty());
+
+        collapse(&db, outage, 2, 60).await;
+        let pings = db
+            .get_pings_subset(SEEDED_SERVER, Some(outage), day)
+            .await
+            .unwrap();
+        assert_eq!(pings.len(), 3);
+        assert!(pings.iter().all(|p| p.id == outage && !p.online));
+
+        // Rows before the cursor still don't come back
+        let pings = db
+            .get_pings_subset(SEEDED_SERVER, Some(online), day)
+            .await
+            .unwrap();
+        assert_eq!(pings.len(), 3);
+    }
+
+    async fn admin_with_sessions(db: &Database) -> i64 {
+        db.ensure_admin_user("alice", "not-a-real-hash")
+            .await
+            .unwrap();
+        let admin = db.get_admin_by_username("alice").await.unwrap().unwrap();
+        db.create_admin_session(admin.id, "token-a").await.unwrap();
+        db.create_admin_session(admin.id, "token-b").await.unwrap();
+        admin.id
+    }
+
+    async fn session_count(db: &Database) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM admin_sessions")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn deleting_an_admin_removes_their_sessions() {
+        let db = TestDb::new().await;
+        let admin_id = admin_with_sessions(&db).await;
+        assert_eq!(session_count(&db).await, 2);
+
+        sqlx::query("DELETE FROM admin_users WHERE id = ?")
+            .bind(admin_id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(session_count(&db).await, 0);
+    }
+
+    #[tokio::test]
+    async fn startup_removes_sessions_orphaned_with_foreign_keys_off() {
+        let db = TestDb::new().await;
+        let admin_id = admin_with_sessions(&db).await;
+
+        // What the sqlite3 shell does by default
+        let mut conn = db.pool.acquire().await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM admin_users WHERE id = ?")
+            .bind(admin_id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("PRAGMA foreign_keys = ON")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+        assert_eq!(session_count(&db).await, 2);
+
+        db.run_migrations().await.unwrap();
+        assert_eq!(session_count(&db).await, 0);
+    }
+
+    #[tokio::test]
+    async fn collapsing_names_the_row_it_folded_into() {
+        let db = TestDb::new().await;
+        let outage = db.insert_ping_result(&ping_at(120, false)).await.unwrap();
+
+        let folded = db
+            .collapse_repeated_offline(SEEDED_SERVER, None)
+            .await
+            .unwrap();
+        assert_eq!(folded, Some(outage));
+
+        // Read back as the repeat that just happened, not the start of the outage
+        let ping = db.get_ping_by_id(outage).await.unwrap().unwrap();
+        assert!(ping.pinged_at_epoch >= chrono::Utc::now().timestamp() - 5);
+
+        db.insert_ping_result(&ping_at(0, true)).await.unwrap();
+        let folded = db
+            .collapse_repeated_offline(SEEDED_SERVER, None)
+            .await
+            .unwrap();
+        assert_eq!(folded, None);
+    }
+
+    fn restored(name: &str, port: i64) -> RestoredServer {
+        RestoredServer {
+            name: name.to_string(),
+            address: "mc.example.com".to_string(),
+            port,
+            edition: Edition::Java,
+            is_pinned: true,
+            settings: ServerUpdate {
+                tags: Some(vec!["Survival".to_string()]),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn restore_servers_sets_pin_and_settings() {
+        let db = TestDb::new().await;
+        let ids = db.restore_servers(&[restored("a", 25565)]).await.unwrap();
+
+        let s = db.get_server_by_id(ids[0]).await.unwrap().unwrap();
+        assert!(s.is_pinned);
+        assert_eq!(s.tag_list(), vec!["survival".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn restore_servers_is_all_or_nothing() {
+        let db = TestDb::new().await;
+        sqlx::query(
+            "CREATE TRIGGER fail_restore BEFORE INSERT ON servers WHEN NEW.name = 'boom'
+             BEGIN SELECT RAISE(ABORT, 'boom'); END",
+        )
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let res = db
+            .restore_servers(&[restored("a", 25565), restored("boom", 25566)])
+            .await;
+        assert!(res.is_err());
+        assert_eq!(db.count_servers(true).await.unwrap(), 1);
+    }
+}
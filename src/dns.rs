@@ -0,0 +1,155 @@
+// Just enough of a DNS client to look up SRV records (`_minecraft._tcp.<host>`), which
+// the system resolver behind `lookup_host` can't do. Asks the first nameserver in
+// /etc/resolv.conf over UDP.
+// https://www.rfc-editor.org/rfc/rfc1035 (message format), https://www.rfc-editor.org/rfc/rfc2782 (SRV)
+
+use rand::RngCore;
+use std::io;
+use std::net::IpAddr;
+use tokio::net::UdpSocket;
+
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const RCODE_NXDOMAIN: u16 = 3;
+
+#[derive(Debug, Clone)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// SRV records for `name`, preferred one first. No records (or no such name) is an empty
+/// list, not an error. The caller is expected to wrap this in a timeout.
+pub async fn lookup_srv(name: &str) -> io::Result<Vec<SrvRecord>> {
+    let nameserver = system_nameserver()?;
+    let bind = if nameserver.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect((nameserver, 53)).await?;
+
+    let id = rand::rngs::OsRng.next_u32() as u16;
+    socket.send(&query(id, name)?).await?;
+    let mut buf = [0u8; 1500];
+    let n = socket.recv(&mut buf).await?;
+
+    let mut records = parse_response(&buf[..n], id)?;
+    // Lowest priority wins, higher weight first among equals
+    records.sort_by_key(|r| (r.priority, u16::MAX - r.weight));
+    Ok(records)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn system_nameserver() -> io::Result<IpAddr> {
+    let conf = std::fs::read_to_string("/etc/resolv.conf")?;
+    conf.lines()
+        .filter_map(|l| l.trim().strip_prefix("nameserver"))
+        // Drop a `%eth0` zone suffix, IpAddr doesn't parse it
+        .filter_map(|ns| ns.trim().split('%').next()?.parse().ok())
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no nameserver in /etc/resolv.conf"))
+}
+
+// Header: id, flags, qdcount=1, ancount, nscount, arcount; then the question
+fn query(id: u16, name: &str) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(18 + name.len());
+    for word in [id, FLAG_RECURSION_DESIRED, 1, 0, 0, 0] {
+        out.extend_from_slice(&word.to_be_bytes());
+    }
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid DNS name",
+            ));
+        }
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+    out.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    out.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(out)
+}
+
+fn read_u16(msg: &[u8], pos: usize) -> io::Result<u16> {
+    msg.get(pos..pos + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| invalid("truncated DNS message"))
+}
+
+// Returns the (possibly compressed) name at `pos` and the position right after it
+fn read_name(msg: &[u8], mut pos: usize) -> io::Result<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Pointers could loop, a real name never needs this many jumps
+    for _ in 0..64 {
+        let len = *msg.get(pos).ok_or_else(|| invalid("truncated DNS name"))? as usize;
+        if len & 0xc0 == 0xc0 {
+            let target = (read_u16(msg, pos)? & 0x3fff) as usize;
+            end.get_or_insert(pos + 2);
+            pos = target;
+        } else if len == 0 {
+            return Ok((labels.join("."), end.unwrap_or(pos + 1)));
+        } else {
+            let label = msg
+                .get(pos + 1..pos + 1 + len)
+                .ok_or_else(|| invalid("truncated DNS label"))?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            pos += 1 + len;
+        }
+    }
+    Err(invalid("DNS name compression loop"))
+}
+
+fn parse_response(msg: &[u8], id: u16) -> io::Result<Vec<SrvRecord>> {
+    if msg.len() < 12 || read_u16(msg, 0)? != id {
+        return Err(invalid("unexpected DNS response"));
+    }
+    let flags = read_u16(msg, 2)?;
+    match flags & 0x000f {
+        0 => {}
+        RCODE_NXDOMAIN => return Ok(Vec::new()),
+        rcode => {
+            return Err(invalid(&format!(
+                "DNS server answered with rcode {}",
+                rcode
+            )));
+        }
+    }
+    let questions = read_u16(msg, 4)?;
+    let answers = read_u16(msg, 6)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(msg, pos)?.1 + 4; // + type and class
+    }
+
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        // name, type(2) class(2) ttl(4) rdlength(2), rdata
+        pos = read_name(msg, pos)?.1;
+        let kind = read_u16(msg, pos)?;
+        let rdlength = read_u16(msg, pos + 8)? as usize;
+        let rdata = pos + 10;
+        if kind == TYPE_SRV {
+            // priority(2) weight(2) port(2) target; the target may point back into msg
+            records.push(SrvRecord {
+                priority: read_u16(msg, rdata)?,
+                weight: read_u16(msg, rdata + 2)?,
+                port: read_u16(msg, rdata + 4)?,
+                target: read_name(msg, rdata + 6)?.0,
+            });
+        }
+        pos = rdata + rdlength;
+    }
+    Ok(records)
+}
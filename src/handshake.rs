@@ -0,0 +1,146 @@
+// craftping always sends protocol -1 ("whatever you speak") in the status handshake,
+// which a few very old or snapshot servers refuse. Instead of re-implementing the ping
+// we wrap the stream and patch the protocol number in that first packet on its way out.
+// https://minecraft.wiki/w/Java_Edition_protocol/Server_List_Ping
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+// Forge only lists its mods (and some modpacks only answer at all) when the client
+// announces itself with a marker after the host name, separated by a NUL
+const FML1_MARKER: &str = "\0FML\0";
+const FML2_MARKER: &str = "\0FML2\0";
+// 1.13, where Forge switched to the FML2 handshake
+const FML2_MIN_PROTOCOL: i64 = 393;
+
+/// Host name for the handshake of a Forge server, FML2 unless the server is pinned to
+/// a protocol from before 1.13.
+pub fn forge_host(address: &str, protocol: Option<i64>) -> String {
+    let marker = match protocol {
+        Some(p) if p < FML2_MIN_PROTOCOL => FML1_MARKER,
+        _ => FML2_MARKER,
+    };
+    format!("{address}{marker}")
+}
+
+// Packet id 0x00 followed by -1 as a VarInt
+const DEFAULT_HANDSHAKE_PREFIX: [u8; 6] = [0x00, 0xff, 0xff, 0xff, 0xff, 0x0f];
+
+/// Stream wrapper that rewrites the handshake protocol version of the first write.
+/// Everything after that (including craftping's legacy fallback) passes through untouched.
+pub struct ProtocolOverride<S> {
+    inner: S,
+    protocol: Option<i32>,
+    // Rewritten first write: bytes, how many are sent, length of the caller's buffer
+    pending: Option<(Vec<u8>, usize, usize)>,
+    done: bool,
+}
+
+impl<S> ProtocolOverride<S> {
+    /// `protocol = None` keeps the library default.
+    pub fn new(inner: S, protocol: Option<i32>) -> Self {
+        Self {
+            inner,
+            protocol,
+            pending: None,
+            done: protocol.is_none(),
+        }
+    }
+}
+
+/// Returns `request` with the handshake protocol replaced, or unchanged if it
+/// doesn't look like craftping's handshake.
+fn rewrite_handshake(request: &[u8], protocol: i32) -> Vec<u8> {
+    let Some((len, header)) = read_varint(request) else {
+        return request.to_vec();
+    };
+    let end = header + len as usize;
+    if len < 0 || end > request.len() || !request[header..].starts_with(&DEFAULT_HANDSHAKE_PREFIX) {
+        return request.to_vec();
+    }
+
+    let mut packet = vec![0x00];
+    write_varint(&mut packet, protocol);
+    packet.extend_from_slice(&request[header + DEFAULT_HANDSHAKE_PREFIX.len()..end]);
+
+    let mut out = Vec::with_capacity(request.len());
+    write_varint(&mut out, packet.len() as i32);
+    out.extend_from_slice(&packet);
+    // The status request packet that craftping sends in the same write
+    out.extend_from_slice(&request[end..]);
+    out
+}
+
+// Returns the value and the number of bytes it took
+fn read_varint(buf: &[u8]) -> Option<(i32, usize)> {
+    let mut value = 0i32;
+    for (i, b) in buf.iter().take(5).enumerate() {
+        value |= ((b & 0x7f) as i32) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+fn write_varint(out: &mut Vec<u8>, value: i32) {
+    let mut v = value as u32;
+    loop {
+        if v & !0x7f == 0 {
+            out.push(v as u8);
+            return;
+        }
+        out.push((v & 0x7f) as u8 | 0x80);
+        v >>= 7;
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for ProtocolOverride<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for ProtocolOverride<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.done {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        }
+        // Rewriting changes the length, so report the caller's whole buffer
+        // as written only once all of the rewritten bytes are out
+        let protocol = this.protocol.unwrap_or(-1);
+        let (bytes, sent, original_len) = this
+            .pending
+            .get_or_insert_with(|| (rewrite_handshake(buf, protocol), 0, buf.len()));
+        while *sent < bytes.len() {
+            let n = ready!(Pin::new(&mut this.inner).poll_write(cx, &bytes[*sent..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            *sent += n;
+        }
+        let written = *original_len;
+        this.pending = None;
+        this.done = true;
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
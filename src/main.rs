@@ -1,37 +1,293 @@
+mod badge;
+mod bedrock;
+mod chart;
+mod config;
 mod database;
+mod dns;
+mod handshake;
+mod metrics;
+mod motd;
+mod net;
+mod password;
+mod players;
+mod query;
+mod webhook;
 
 use axum::{
     Json, Router,
-    extract::{Form, Path, Query, State},
-    http::{HeaderMap, StatusCode, header},
-    response::{IntoResponse, Redirect, Response},
+    body::Body,
+    extract::{ConnectInfo, Form, Path, Query, Request, State, rejection::JsonRejection},
+    http::{HeaderMap, Method, StatusCode, header},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Redirect, Response},
     routing::{delete, get, post},
 };
+use config::{Config, CookieSecure, LogFormat};
 use craftping::tokio::ping;
-use database::{AdminUser, Database, PingResult};
+use database::{
+    AdminUser, CheckType, Database, DbError, Edition, NewPing, PingErrorKind, PingResult,
+    PingSource, PoolStats, RestoredServer, Server, ServerEvent, ServerUpdate, normalize_address,
+    normalize_tag,
+};
+use handshake::ProtocolOverride;
+use metrics::LatencyHistograms;
+use net::PeerAddr;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::env;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::signal;
+use tokio::sync::{mpsc, watch};
 use tokio::time::{Duration, sleep};
+use tokio_util::io::ReaderStream;
+use tower_http::cors::{self, AllowOrigin, CorsLayer};
 use tower_http::services::ServeDir;
+use tracing::{Instrument, error, info, info_span, warn};
+use webhook::Notifier;
 
-use argon2::{
-    Argon2,
-    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Params, Version};
 use rand::{RngCore, rngs::OsRng};
 
 #[derive(Clone)]
 struct AppState {
     db: Database,
+    config: Arc<Config>,
+    servers_cache: Arc<Mutex<Option<CachedServers>>>,
+    ping_cycles: Arc<Mutex<PingCycleStats>>,
+    // Rendered chart.png per (server, window in seconds)
+    chart_cache: Arc<Mutex<HashMap<(i64, u64), CachedChart>>>,
+    // One running ping per server, holding the row id of the last one it stored
+    ping_slots: Arc<Mutex<HashMap<i64, PingSlot>>>,
+    // Unix time the process started, the pinger gets STALE_AFTER_SECS from here to report in
+    started_at: i64,
+    notifier: Notifier,
+    latency_histograms: Arc<LatencyHistograms>,
+}
+
+type PingSlot = Arc<tokio::sync::Mutex<Option<i64>>>;
+
+struct CachedChart {
+    built_at: Instant,
+    png: Vec<u8>,
+}
+
+// How long a rendered chart.png is reused; a ping cycle is 10 minutes, so little changes
+const CHART_CACHE_SECS: u64 = 60;
+// Points on the chart.png line
+const CHART_BUCKETS: i64 = 120;
+
+// Durations of recent background ping cycles, for /api/stats
+#[derive(Default)]
+struct PingCycleStats {
+    completed: u64,
+    last_servers: usize,
+    recent: VecDeque<Duration>,
+    // Unix time the last cycle finished
+    last_completed_at: Option<i64>,
+}
+
+// Cycles kept for avg_cycle_secs (4 hours at the default interval)
+const PING_CYCLE_HISTORY: usize = 24;
+
+// Assembled /api/servers payload, reused until it expires or data changes
+struct CachedServers {
+    built_at: Instant,
+    servers: Vec<LightServerApi>,
+}
+
+impl AppState {
+    fn invalidate_servers_cache(&self) {
+        *self.servers_cache.lock().unwrap() = None;
+    }
+
+    fn ping_slot(&self, server_id: i64) -> PingSlot {
+        self.ping_slots
+            .lock()
+            .unwrap()
+            .entry(server_id)
+            .or_default()
+            .clone()
+    }
+
+    // Slots of deleted servers would otherwise stay in the map for the life of the process
+    fn forget_ping_slots(&self, server_ids: &[i64]) {
+        let mut slots = self.ping_slots.lock().unwrap();
+        for id in server_ids {
+            slots.remove(id);
+        }
+    }
+
+    fn record_ping_cycle(&self, servers: usize, took: Duration) {
+        let mut stats = self.ping_cycles.lock().unwrap();
+        stats.completed += 1;
+        stats.last_servers = servers;
+        if stats.recent.len() == PING_CYCLE_HISTORY {
+            stats.recent.pop_front();
+        }
+        stats.recent.push_back(took);
+        stats.last_completed_at = Some(now_epoch());
+    }
+}
+
+// Error with a human readable message, rendered as `{ "error": "..." }`
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
+        let message = status.canonical_reason().unwrap_or("error").to_string();
+        Self { status, message }
+    }
+}
+
+impl From<DbError> for ApiError {
+    fn from(e: DbError) -> Self {
+        match e {
+            DbError::NotFound => StatusCode::NOT_FOUND.into(),
+            // The constraint text names tables and columns, keep it in the log
+            DbError::Conflict(msg) => {
+                warn!("Database conflict: {}", msg);
+                ApiError::new(StatusCode::CONFLICT, "conflicts with an existing record")
+            }
+            DbError::Connection(_) => {
+                error!("Database error: {}", e);
+                ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "database unavailable")
+            }
+            DbError::Other(_) => {
+                error!("Database error: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into()
+            }
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        #[derive(Serialize)]
+        struct Body {
+            error: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            request_id: Option<String>,
+        }
+        (
+            self.status,
+            Json(Body {
+                error: self.message,
+                request_id: REQUEST_ID.try_with(String::clone).ok(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+tokio::task_local! {
+    // Set by `log_requests` for the duration of a request, so error bodies can name it
+    static REQUEST_ID: String;
 }
 
 #[derive(Deserialize)]
 struct HistoryParams {
     range: Option<String>, // "day", "week", "month"
     since_id: Option<i64>, // For incremental updates
+    only_transitions: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct RangeParams {
+    range: Option<String>, // "day", "week", "month"
+}
+
+#[derive(Deserialize)]
+struct BadgeParams {
+    format: Option<String>, // "svg" (default) or "shields"
+}
+
+#[derive(Deserialize)]
+struct OfflineParams {
+    // Only report servers that have been down at least this long
+    grace_secs: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct TimelineParams {
+    range: Option<String>,
+    buckets: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    #[serde(default)]
+    q: String,
+}
+
+#[derive(Deserialize)]
+struct UptimeParams {
+    range: Option<String>,
+    #[serde(default)]
+    include_manual: bool,
+}
+
+#[derive(Deserialize)]
+struct PingNowParams {
+    #[serde(default)]
+    wait: bool,
+}
+
+#[derive(Deserialize)]
+struct LatencyTestParams {
+    count: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct AtParams {
+    time: String, // RFC 3339
+}
+
+#[derive(Deserialize)]
+struct ExportParams {
+    format: Option<String>, // only "ndjson" so far
+}
+
+#[derive(Deserialize)]
+struct MuteParams {
+    // 0 unmutes
+    minutes: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct CompactParams {
+    // Only pings older than this are compacted
+    older_than_days: Option<i64>,
+    // Width of the averaged points for long online stretches
+    chunk_hours: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct HeatmapParams {
+    range: Option<String>,
+    // Also return the 7x24 grid, not just the 24 hours
+    #[serde(default)]
+    by_weekday: bool,
+    // Minutes east of UTC for the hour/day boundaries, e.g. 120 for UTC+2
+    tz_offset: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,6 +295,142 @@ struct CreateServerJson {
     name: String,
     address: String,
     port: Option<i64>,
+    #[serde(default)]
+    allow_duplicate: bool,
+    enable_query: Option<bool>,
+    query_port: Option<i64>,
+    protocol_hint: Option<i64>,
+    modded: Option<bool>,
+    tags: Option<Vec<String>>,
+    #[serde(default)]
+    edition: Edition,
+    #[serde(default)]
+    check_type: CheckType,
+    color: Option<String>,
+    is_public: Option<bool>,
+    webhook_url: Option<String>,
+    player_alert_threshold: Option<i64>,
+}
+
+/// One server in `/api/admin/servers/export`, settings only. Restoring it creates a
+/// new server, so the id isn't part of it.
+#[derive(Debug, Serialize, Deserialize)]
+struct ServerDefinition {
+    name: String,
+    address: String,
+    port: i64,
+    #[serde(default)]
+    edition: Edition,
+    #[serde(default)]
+    check_type: CheckType,
+    #[serde(default)]
+    enable_query: bool,
+    query_port: Option<i64>,
+    protocol_hint: Option<i64>,
+    #[serde(default)]
+    modded: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+    color: Option<String>,
+    #[serde(default = "default_true")]
+    is_public: bool,
+    #[serde(default)]
+    is_pinned: bool,
+    webhook_url: Option<String>,
+    player_alert_threshold: Option<i64>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl ServerDefinition {
+    fn from_server(s: Server) -> Self {
+        Self {
+            tags: s.tag_list(),
+            name: s.name,
+            address: s.address,
+            port: s.port,
+            edition: s.edition,
+            check_type: s.check_type,
+            enable_query: s.enable_query,
+            query_port: s.query_port,
+            protocol_hint: s.protocol_hint,
+            modded: s.modded,
+            color: s.color,
+            is_public: s.is_public,
+            is_pinned: s.is_pinned,
+            webhook_url: s.webhook_url,
+            player_alert_threshold: s.player_alert_threshold,
+        }
+    }
+
+    // Everything but name, address, port and pinning, which are set separately
+    fn settings(&self) -> ServerUpdate {
+        ServerUpdate {
+            check_type: Some(self.check_type),
+            enable_query: Some(self.enable_query),
+            query_port: Some(self.query_port),
+            protocol_hint: Some(self.protocol_hint),
+            modded: Some(self.modded),
+            tags: Some(self.tags.clone()),
+            color: Some(self.color.clone()),
+            is_public: Some(self.is_public),
+            webhook_url: Some(self.webhook_url.clone()),
+            player_alert_threshold: Some(self.player_alert_threshold),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ServersSnapshot {
+    #[serde(default)]
+    exported_at: String,
+    servers: Vec<ServerDefinition>,
+}
+
+#[derive(Debug, Serialize)]
+struct RestoreServersResponse {
+    created: Vec<i64>,
+    // "address:port" of servers that were already tracked
+    skipped: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MigrateServerJson {
+    address: String,
+    port: Option<i64>,
+    // Ping the new endpoint right away so the graph has a point at the switch-over
+    marker_ping: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteServersJson {
+    ids: Vec<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct DeleteServersResponse {
+    deleted: usize,
+    // Requested ids that didn't exist (nothing to delete)
+    not_found: Vec<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReorderServersJson {
+    // Desired display order, first = top
+    ids: Vec<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BulkPingsJson {
+    pings: Vec<NewPing>,
+}
+
+#[derive(Debug, Serialize)]
+struct BulkPingsResponse {
+    inserted: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,6 +439,12 @@ struct LoginForm {
     password: String,
 }
 
+#[derive(Deserialize)]
+struct ChangePasswordJson {
+    current_password: String,
+    new_password: String,
+}
+
 #[derive(Debug, Serialize)]
 struct AuthMeResponse {
     #[serde(rename = "isAdmin")]
@@ -58,6 +456,263 @@ struct SimpleResponse {
     success: bool,
 }
 
+#[derive(Debug, Serialize)]
+struct PercentilesResponse {
+    samples: usize,
+    p50: Option<i64>,
+    p90: Option<i64>,
+    p99: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct TimelineBucket {
+    start: String,
+    end: String,
+    samples: i64,
+    // None when no pings landed in this bucket
+    online_ratio: Option<f64>,
+    // No pings at all, e.g. the pinger was down; not the same as offline
+    no_data: bool,
+}
+
+#[derive(Serialize)]
+struct AdminConfigResponse<'a> {
+    #[serde(flatten)]
+    config: &'a Config,
+    ping_interval_secs: u64,
+    ping_retention_days: i64,
+    // Never the value itself, just whether the built-in default is in use
+    admin_password: &'static str,
+    // "***" when set, the URL carries the webhook's token
+    webhook_url: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct StorageResponse {
+    size_bytes: u64,
+    wal_size_bytes: Option<u64>,
+    tables: BTreeMap<String, i64>,
+    // Ping rows added per day over the last STORAGE_RATE_DAYS
+    ping_rows_per_day: f64,
+    // Size over all rows, so indexes and the small tables are priced in
+    bytes_per_row: Option<f64>,
+    ping_retention_days: i64,
+    // Size once the retention window is full at the current rate, when cleanup keeps it flat
+    projected_size_bytes: u64,
+}
+
+// Window the ping rate for /admin/storage is measured over
+const STORAGE_RATE_DAYS: i64 = 7;
+
+// How many outages /info includes
+const RECENT_INCIDENTS: usize = 5;
+
+#[derive(Debug, Serialize)]
+struct ServerInfoResponse {
+    #[serde(flatten)]
+    server: ServerApi,
+    last_ping: Option<PingResult>,
+    recent_incidents: Vec<Incident>,
+    // Alerts are silenced until then, both None when not muted
+    muted_until: Option<String>,
+    mute_remaining_secs: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct MuteResponse {
+    muted_until: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct PinResponse {
+    is_pinned: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct UptimeResponse {
+    // online / (online + offline), gaps don't count either way
+    uptime: Option<f64>,
+    #[serde(flatten)]
+    coverage: Coverage,
+}
+
+#[derive(Debug, Serialize)]
+struct CompactResponse {
+    deleted: u64,
+    inserted: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct GroupStatusResponse {
+    tag: String,
+    servers: usize,
+    online: usize,
+    // Sum of the latest player counts of the online servers
+    players: i64,
+    // Share of online pings across the group over the range, None without pings
+    uptime: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct AvailabilityResponse {
+    // First and last online ping in the range
+    first_online: Option<String>,
+    last_online: Option<String>,
+    // Longest online/offline stretch, measured like incidents (up to the next flip or now)
+    longest_uptime_secs: i64,
+    longest_downtime_secs: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct LatencyTestResponse {
+    // Round trip of each status ping in order, null where it failed or timed out
+    samples_ms: Vec<Option<f64>>,
+    min_ms: Option<f64>,
+    avg_ms: Option<f64>,
+    max_ms: Option<f64>,
+    // Mean difference between consecutive successful samples
+    jitter_ms: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct VersionCount {
+    version: String,
+    servers: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct FlappingResponse {
+    // Online <-> offline flips between consecutive pings in the range
+    transitions: usize,
+    // Flips allowed in the range before it counts as flapping
+    threshold: u64,
+    flapping: bool,
+}
+
+// Stats of one window for /compare
+#[derive(Debug, Serialize)]
+struct PeriodStats {
+    start: String,
+    end: String,
+    // Same definition as /uptime
+    uptime: Option<f64>,
+    // Over online pings that reported a player count
+    avg_players: Option<f64>,
+    peak_players: Option<i64>,
+}
+
+// current - previous; None when either side has no data
+#[derive(Debug, Serialize)]
+struct PeriodDelta {
+    uptime: Option<f64>,
+    avg_players: Option<f64>,
+    // Relative change of avg_players in percent, None if the previous average was 0
+    avg_players_pct: Option<f64>,
+    peak_players: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct CompareResponse {
+    current: PeriodStats,
+    previous: PeriodStats,
+    delta: PeriodDelta,
+}
+
+// One-off connectivity check for /diagnostics, nothing of it is stored
+#[derive(Debug, Serialize)]
+struct DiagnosticsResponse {
+    dns_ok: bool,
+    resolved_ips: Vec<String>,
+    // Preferred `_minecraft._tcp` record as "host:port" (Java only). Pings use the
+    // configured address and port as is, so a differing target can explain an outage
+    srv_target: Option<String>,
+    // None for Bedrock, which only speaks UDP
+    tcp_ok: Option<bool>,
+    ping_ok: bool,
+    // Every step that failed, in order
+    error: Option<String>,
+}
+
+// One stretch of consecutive pings reporting the same MOTD
+#[derive(Debug, Serialize)]
+struct MotdPeriod {
+    motd: String,
+    first_seen: String,
+    last_seen: String,
+    samples: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct HeatmapResponse {
+    // Average players per hour of day 0-23, None where there were no online pings
+    hours: Vec<Option<f64>>,
+    // days[weekday][hour], Sunday first, only with by_weekday=true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    days: Option<Vec<Vec<Option<f64>>>>,
+}
+
+// https://shields.io/badges/endpoint-badge
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShieldsEndpoint {
+    schema_version: u8,
+    label: String,
+    message: String,
+    color: String,
+}
+
+#[derive(Debug, Serialize)]
+struct OfflineServerApi {
+    id: i64,
+    name: String,
+    address: String,
+    port: i64,
+    down_since: String,
+    down_secs: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct InfoResponse {
+    name: Option<String>,
+    version: &'static str,
+    server_count: i64,
+}
+
+// Switches the UI can adapt to, all derived from the Config
+#[derive(Debug, Serialize)]
+struct FeaturesResponse {
+    // Login and admin endpoints are available (not READ_ONLY)
+    login: bool,
+    auth_required_for_reads: bool,
+    // A global WEBHOOK_URL is set; per-server webhooks work either way
+    webhooks: bool,
+    metrics: bool,
+    cors: bool,
+    ip_allowlist: bool,
+    collapse_repeated_offline: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct StatsResponse {
+    ping_interval_secs: u64,
+    ping_cycles: u64,
+    last_cycle_servers: usize,
+    last_cycle_secs: Option<f64>,
+    avg_cycle_secs: Option<f64>,
+    last_cycle_at: Option<String>,
+    // Newest ping of any server, None while nothing has been pinged yet
+    last_ping_age_secs: Option<i64>,
+    // A cycle finished within STALE_AFTER_SECS (counting from startup before the first one)
+    pinger_healthy: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    wal_size_bytes: Option<u64>,
+    pools: PoolStats,
+}
+
 #[derive(Debug, Serialize)]
 struct ServerApi {
     pub id: i64,
@@ -66,102 +721,653 @@ struct ServerApi {
     pub port: i64,
     pub created_at: String,
     pub last_online: bool,
+    pub status: ServerStatus,
+    pub enable_query: bool,
+    pub query_port: Option<i64>,
+    pub protocol_hint: Option<i64>,
+    pub modded: bool,
+    pub tags: Vec<String>,
+    pub edition: Edition,
+    pub check_type: CheckType,
+    pub color: Option<String>,
+    pub sort_order: i64,
+    pub is_public: bool,
+    pub is_pinned: bool,
+    // Seconds since the newest ping, None before the first one
+    pub last_ping_age_secs: Option<i64>,
+    // The URL itself isn't shown, it works as a credential
+    pub has_webhook_url: bool,
+    pub player_alert_threshold: Option<i64>,
+}
+
+impl ServerApi {
+    fn from_server(s: Server, last: Option<&PingResult>) -> Self {
+        let tags = s.tag_list();
+        Self {
+            id: s.id,
+            name: s.name,
+            address: s.address,
+            port: s.port,
+            created_at: s.created_at,
+            last_online: last.is_some_and(|p| p.online),
+            status: ServerStatus::of(last),
+            last_ping_age_secs: last.map(|p| now_epoch() - p.pinged_at_epoch),
+            enable_query: s.enable_query,
+            query_port: s.query_port,
+            protocol_hint: s.protocol_hint,
+            modded: s.modded,
+            edition: s.edition,
+            check_type: s.check_type,
+            color: s.color,
+            sort_order: s.sort_order,
+            is_public: s.is_public,
+            is_pinned: s.is_pinned,
+            has_webhook_url: s.webhook_url.is_some(),
+            player_alert_threshold: s.player_alert_threshold,
+            tags,
+        }
+    }
 }
 
+// Background ping cadence (ten minutes), cycles are aligned to multiples of it
+const PING_INTERVAL_SECS: u64 = 600;
+// Pings older than this are deleted by the maintenance task
+const PING_RETENTION_DAYS: i64 = 60;
+
 #[tokio::main]
 async fn main() {
+    // Before Config::from_env so its warnings already come out in the right format.
+    // An invalid LOG_FORMAT falls back to text here and is reported by Config.
+    init_logging(
+        env::var("LOG_FORMAT")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or_default(),
+    );
+    let config = Config::from_env();
+
     // 1. Initialize Database
-    let db_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://sqlite.db".to_string());
-    let db = Database::init(&db_url)
-        .await
-        .expect("failed to initialize database");
+    let db = match Database::init(
+        &config.database_url,
+        config.wal_autocheckpoint_pages,
+        config.db_read_pool_size,
+    )
+    .await
+    {
+        Ok(db) => db,
+        Err(e) if e.is_unwritable() => {
+            // Usually a read-only volume or a directory owned by another user
+            error!(
+                "Cannot create/open database at {}: {}",
+                database::database_path(&config.database_url),
+                e
+            );
+            warn!(
+                "Check that this user can create and write to that directory, or point DATA_DIR/DATABASE_URL elsewhere."
+            );
+            std::process::exit(1);
+        }
+        Err(e) => panic!("failed to initialize database: {e:?}"),
+    };
 
-    // 2. Create default admin
-    init_default_admin(&db).await;
+    // 2. Create default admin (unless admins are provisioned externally)
+    if config.create_default_admin {
+        init_default_admin(&db, &config).await;
+    }
 
     let db_for_shutdown = db.clone();
-    let state = AppState { db };
+    let read_only_mode = config.read_only;
+    let drain_timeout = config.shutdown_timeout;
+    let api_only = config.api_only;
+    let bind_addr = config.bind_addr.clone();
+    let allowlist_without_proxy = !config.admin_ip_allowlist.is_empty() && !config.trust_proxy;
+    let notifier = Notifier::new(config.webhook_url.clone());
+    let latency_histograms = Arc::new(LatencyHistograms::new(config.latency_buckets_ms.clone()));
+    let state = AppState {
+        db,
+        config: Arc::new(config),
+        servers_cache: Arc::new(Mutex::new(None)),
+        ping_cycles: Arc::new(Mutex::new(PingCycleStats::default())),
+        chart_cache: Arc::new(Mutex::new(HashMap::new())),
+        ping_slots: Arc::new(Mutex::new(HashMap::new())),
+        started_at: now_epoch(),
+        notifier,
+        latency_histograms,
+    };
+
+    // Flipped to the time of the signal on Ctrl+C / SIGTERM, everything that should stop watches it
+    let (shutdown_tx, shutdown_rx) = watch::channel(None);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down...");
+        let _ = shutdown_tx.send(Some(Instant::now()));
+    });
 
     // 3. Background Task
     let bg_state = state.clone();
+    let mut bg_shutdown = shutdown_rx.clone();
     let background_task_handle = tokio::spawn(async move {
-        const CLEANUP_INTERVAL: u64 = 60 * 60 * 24; // 24H
+        // Get data on the board right away instead of waiting for the next boundary
+        let mut startup_pinged = false;
+        if bg_state.config.ping_on_startup {
+            match ping_all_servers_concurrently(&bg_state).await {
+                Ok(()) => startup_pinged = true,
+                Err(e) => error!("Startup ping error: {:?}", e),
+            }
+        }
+
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
-        let interval = 600; // Ten minutes 600 seconds I should probably change this to be an env variable
-        let seconds_past = now % interval;
-        let wait = interval - seconds_past;
-        sleep(Duration::from_secs(wait)).await;
-
-        // Track when we last ran DB cleanup
-        let mut last_cleanup = SystemTime::now();
+        let interval = PING_INTERVAL_SECS;
+        let wait = first_cycle_wait(now, interval, startup_pinged);
+        if sleep_unless_shutdown(Duration::from_secs(wait), &mut bg_shutdown).await {
+            return;
+        }
 
         // Ping each server every ten minutes
         loop {
             if let Err(e) = ping_all_servers_concurrently(&bg_state).await {
-                eprintln!("Background ping error: {:?}", e);
+                error!("Background ping error: {:?}", e);
             }
-            if last_cleanup.elapsed().unwrap() >= Duration::from_secs(CLEANUP_INTERVAL) {
-                if let Err(e) = bg_state.db.cleanup_old_pings(60).await {
-                    eprintln!("Failed to cleanup old pings: {:?}", e);
-                }
-                last_cleanup = SystemTime::now();
+            // Only stops between cycles, so a running cycle always finishes
+            if sleep_unless_shutdown(Duration::from_secs(interval), &mut bg_shutdown).await {
+                return;
             }
-            sleep(Duration::from_secs(interval)).await;
+        }
+    });
+
+    // Housekeeping on its own cadence (MAINTENANCE_INTERVAL_SECS), a slow cleanup
+    // doesn't delay pings and the ping interval doesn't dictate cleanups
+    let maint_state = state.clone();
+    let mut maint_shutdown = shutdown_rx.clone();
+    let maintenance_task_handle = tokio::spawn(async move {
+        let interval = maint_state.config.maintenance_interval;
+        while !sleep_unless_shutdown(interval, &mut maint_shutdown).await {
+            run_maintenance(&maint_state).await;
         }
     });
 
     // 4. Router
+    let cors = cors_layer(&state.config.cors_origins);
+
     // We put API routes under /api so they don't clash with file names
-    let api_routes = Router::new()
+    // Stays reachable for probes even when AUTH_REQUIRED_FOR_READS is set
+    let health_api = Router::new().route("/health", get(health));
+
+    // Read-only endpoints, open to everyone unless AUTH_REQUIRED_FOR_READS
+    let public_api = Router::new()
+        .route("/stats", get(stats))
+        .route("/info", get(instance_info))
+        .route("/features", get(features))
+        .route("/servers", get(list_servers))
+        .route("/servers/offline", get(list_offline_servers))
+        .route("/servers/search", get(search_servers))
+        .route("/groups/{tag}/status", get(group_status))
+        .route("/versions", get(list_versions))
+        .route("/network/players", get(network_players))
+        .route("/metrics", get(prometheus_metrics))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth_required_for_reads,
+        ));
+
+    // Everything about a single server, 404 for visitors when the server is private
+    let public_server_api = Router::new()
+        .route("/servers/{id}/query", get(server_query_data))
+        .route("/servers/{id}/mods", get(server_mods))
+        .route("/servers/{id}/players", get(server_players))
+        .route("/servers/{id}/pings", get(list_server_ping_history))
+        .route("/servers/{id}/percentiles", get(server_percentiles))
+        .route("/servers/{id}/timeline", get(server_timeline))
+        .route("/servers/{id}/chart.png", get(server_chart_png))
+        .route("/servers/{id}/heatmap", get(server_heatmap))
+        .route("/servers/{id}/motd-history", get(server_motd_history))
+        .route("/servers/{id}/uptime", get(server_uptime))
+        .route("/servers/{id}/info", get(server_info))
+        .route("/servers/{id}/latest", get(latest_ping))
+        .route("/servers/{id}/at", get(ping_at))
+        .route("/servers/{id}/incidents", get(list_server_incidents))
+        .route("/servers/{id}/flapping", get(server_flapping))
+        .route("/servers/{id}/availability", get(server_availability))
+        .route("/servers/{id}/compare", get(compare_ranges))
+        .route("/servers/{id}/badge", get(server_badge))
+        .route("/servers/{id}/events", get(list_server_events))
+        .route("/servers/{id}/export", get(export_server_history))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            hide_private_servers,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth_required_for_reads,
+        ));
+
+    // Endpoints that need an admin session, also subject to ADMIN_IP_ALLOWLIST and READ_ONLY
+    let admin_api = Router::new()
         .route("/auth/me", get(auth_me))
-        .route("/servers", get(list_servers).post(create_server_json))
-        .route("/servers/{id}", delete(delete_server))
+        .route("/auth/password", post(change_password))
+        .route("/servers", post(create_server_json).delete(delete_servers))
+        .route("/servers/reorder", post(reorder_servers))
+        .route("/servers/{id}", delete(delete_server).patch(update_server))
         .route(
             "/servers/{id}/ping",
             get(ping_and_store).post(ping_and_store),
         )
-        .route("/servers/{id}/pings", get(list_server_ping_history))
+        .route("/servers/{id}/pings/bulk", post(bulk_insert_pings))
+        .route("/servers/{id}/migrate", post(migrate_server))
+        .route("/servers/{id}/compact", post(compact_server_history))
+        .route("/servers/{id}/mute", post(mute_server))
+        .route("/servers/{id}/pin", post(toggle_pin))
+        .route("/servers/{id}/diagnostics", get(server_diagnostics))
+        .route("/servers/{id}/latency-test", post(latency_test))
+        .route("/admin/backup", get(download_backup))
+        .route("/admin/servers/export", get(export_servers))
+        .route("/admin/servers/restore", post(restore_servers))
+        .route("/admin/config", get(admin_config))
+        .route("/admin/storage", get(admin_storage))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            admin_ip_allowlist,
+        ))
+        .route_layer(middleware::from_fn_with_state(state.clone(), read_only));
+
+    let api_routes = health_api
+        .merge(public_api)
+        .merge(public_server_api)
+        .merge(admin_api)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            request_timeout,
+        ))
         .with_state(state.clone());
 
     // Auth routes need state too
     let auth_routes = Router::new()
         .route("/login", post(handle_login))
         .route("/logout", get(handle_logout))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            admin_ip_allowlist,
+        ))
+        .route_layer(middleware::from_fn_with_state(state.clone(), read_only))
         .with_state(state);
 
     let app = Router::new()
         .nest("/api", api_routes)
-        .nest("/auth", auth_routes) // Note: Login form POSTs to /auth/login now
+        .nest("/auth", auth_routes); // Note: Login form POSTs to /auth/login now
+
+    // ServeDir would quietly 404 everything if the frontend wasn't deployed
+    let app = if api_only {
+        app.route("/", get(api_index)).fallback(api_not_found)
+    } else if std::path::Path::new(STATIC_DIR)
+        .join("index.html")
+        .is_file()
+    {
         // This serves index.html, style.css, script.js, images/, etc automatically
-        .fallback_service(ServeDir::new("static"));
+        app.fallback_service(ServeDir::new(STATIC_DIR))
+    } else {
+        warn!("{STATIC_DIR}/index.html not found, the web UI is not deployed.");
+        warn!("Start the binary from the directory that contains {STATIC_DIR}/ and restart.");
+        warn!("The API keeps working, every other page answers 503 until then.");
+        app.fallback(missing_static_assets)
+    };
 
-    let listener = TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    // Outermost, so preflights are answered before auth/allowlist/read-only checks
+    let app = match cors {
+        Some(cors) => app.layer(cors),
+        None => app,
+    };
+    // Around everything else so rejected and preflight requests get logged too
+    let app = app.layer(middleware::from_fn(log_requests));
 
-    println!("Server running on http://0.0.0.0:3000");
-    let is_prod = env::var("APP_ENV").unwrap_or_default() == "production";
-    if !is_prod {
-        println!("Press Ctrl+C to stop.");
+    let unix_path = bind_addr.strip_prefix("unix:");
+    if unix_path.is_some() && allowlist_without_proxy {
+        warn!(
+            "ADMIN_IP_ALLOWLIST needs TRUST_PROXY=true on a Unix socket, admin access will be refused."
+        );
     }
+    let announce = |addr: String| {
+        info!("Server running on {}", addr);
+        if read_only_mode {
+            info!("READ_ONLY is set, admin endpoints and login are disabled.");
+        }
+        let is_prod = env::var("APP_ENV").unwrap_or_default() == "production";
+        if !is_prod {
+            info!("Press Ctrl+C to stop.");
+        }
+    };
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
-
-    println!("Aborting background tasks.");
-    background_task_handle.abort();
-
-    println!("Closing database...");
+    let service = app.into_make_service_with_connect_info::<PeerAddr>();
+    match unix_path {
+        Some(path) => {
+            let listener = bind_unix_socket(path);
+            announce(format!("unix:{}", path));
+            let server = axum::serve(listener, service)
+                .with_graceful_shutdown(shutdown_requested(shutdown_rx.clone()));
+            serve_with_drain(server.into_future(), shutdown_rx.clone(), drain_timeout).await;
+            let _ = std::fs::remove_file(path);
+        }
+        None => {
+            let listener = TcpListener::bind(&bind_addr).await.unwrap();
+            announce(format!("http://{}", bind_addr));
+            let server = axum::serve(listener, service)
+                .with_graceful_shutdown(shutdown_requested(shutdown_rx.clone()));
+            serve_with_drain(server.into_future(), shutdown_rx.clone(), drain_timeout).await;
+        }
+    }
+
+    // Both stop at their next sleep; only a stuck ping cycle or cleanup needs the abort
+    let aborts = [
+        background_task_handle.abort_handle(),
+        maintenance_task_handle.abort_handle(),
+    ];
+    let finished = async {
+        let _ = background_task_handle.await;
+        let _ = maintenance_task_handle.await;
+    };
+    if tokio::time::timeout(drain_timeout, finished).await.is_err() {
+        info!("Background tasks still busy, aborting them.");
+        aborts.iter().for_each(|a| a.abort());
+    }
+
+    info!("Closing database...");
     db_for_shutdown.close().await;
-    println!("Database closed, Bye!");
+    info!("Database closed, Bye!");
+}
+
+// One housekeeping pass: pings past retention, expired sessions, optional WAL truncation
+async fn run_maintenance(state: &AppState) {
+    let started = Instant::now();
+    let pings = state
+        .db
+        .cleanup_old_pings(PING_RETENTION_DAYS)
+        .await
+        .inspect_err(|e| error!("Failed to cleanup old pings: {:?}", e));
+    let sessions = state
+        .db
+        .cleanup_expired_sessions(state.config.session_ttl.as_secs() as i64)
+        .await
+        .inspect_err(|e| error!("Failed to cleanup expired sessions: {:?}", e));
+    let checkpointed = state.config.wal_checkpoint_truncate
+        && state
+            .db
+            .checkpoint_wal()
+            .await
+            .inspect_err(|e| error!("Failed to checkpoint WAL: {:?}", e))
+            .is_ok();
+    info!(
+        "Maintenance: removed {} old pings and {} expired sessions{} in {:.1}s",
+        pings.map_or("?".to_string(), |n| n.to_string()),
+        sessions.map_or("?".to_string(), |n| n.to_string()),
+        if checkpointed { ", WAL truncated" } else { "" },
+        started.elapsed().as_secs_f64()
+    );
+}
+
+// CORS for CORS_ORIGINS. Explicit origins may send the session cookie, `*` may not
+// (browsers refuse credentials with a wildcard origin).
+fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+    let layer = CorsLayer::new()
+        .allow_methods([
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([header::CONTENT_TYPE])
+        .max_age(Duration::from_secs(3600));
+    if origins.iter().any(|o| o == "*") {
+        return Some(layer.allow_origin(cors::Any));
+    }
+    let origins: Vec<header::HeaderValue> = origins
+        .iter()
+        .filter_map(|o| match o.parse() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                warn!("Ignoring invalid CORS origin: {:?}", o);
+                None
+            }
+        })
+        .collect();
+    Some(
+        layer
+            .allow_origin(AllowOrigin::list(origins))
+            .allow_credentials(true),
+    )
+}
+
+// Binds BIND_ADDR=unix:/path. A socket file left over from an unclean exit is removed
+// first; anything else at that path is left alone and binding fails instead.
+#[cfg(unix)]
+fn bind_unix_socket(path: &str) -> tokio::net::UnixListener {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(meta) = std::fs::symlink_metadata(path)
+        && meta.file_type().is_socket()
+    {
+        let _ = std::fs::remove_file(path);
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .unwrap_or_else(|e| panic!("failed to bind unix socket {}: {}", path, e));
+    // Owner and group (e.g. a reverse proxy added to our group) may connect
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o660))
+        .unwrap_or_else(|e| panic!("failed to set permissions on {}: {}", path, e));
+    listener
+}
+
+#[cfg(not(unix))]
+fn bind_unix_socket(_path: &str) -> TcpListener {
+    panic!("BIND_ADDR=unix:... is only supported on Unix");
+}
+
+// Frontend files, relative to the working directory
+const STATIC_DIR: &str = "static";
+
+// Served in place of the UI when STATIC_DIR is missing, so the cause is obvious
+async fn missing_static_assets() -> Response {
+    let page = format!(
+        "<!doctype html><title>Web UI not deployed</title>\
+         <h1>Web UI not deployed</h1>\
+         <p>The server is running, but <code>{STATIC_DIR}/index.html</code> was not found \
+         in its working directory.</p>\
+         <p>Copy the <code>{STATIC_DIR}/</code> folder from the repository next to where the \
+         binary is started, then restart it. The JSON API under <code>/api</code> works meanwhile.</p>"
+    );
+    (StatusCode::SERVICE_UNAVAILABLE, Html(page)).into_response()
+}
+
+// Public endpoints listed at `/` in API_ONLY mode
+const API_INDEX: &[&str] = &[
+    "GET /api/health",
+    "GET /api/stats",
+    "GET /api/info",
+    "GET /api/servers",
+    "GET /api/servers/offline",
+    "GET /api/servers/search?q=",
+    "GET /api/servers/{id}/pings",
+    "GET /api/servers/{id}/percentiles",
+    "GET /api/servers/{id}/timeline",
+    "GET /api/servers/{id}/chart.png",
+    "GET /api/servers/{id}/heatmap",
+    "GET /api/servers/{id}/motd-history",
+    "GET /api/servers/{id}/uptime",
+    "GET /api/servers/{id}/info",
+    "GET /api/servers/{id}/latest",
+    "GET /api/servers/{id}/at?time=",
+    "GET /api/servers/{id}/incidents",
+    "GET /api/servers/{id}/flapping",
+    "GET /api/servers/{id}/availability",
+    "GET /api/servers/{id}/compare",
+    "GET /api/servers/{id}/events",
+    "GET /api/servers/{id}/query",
+    "GET /api/servers/{id}/mods",
+    "GET /api/servers/{id}/players",
+    "GET /api/servers/{id}/badge",
+    "GET /api/servers/{id}/export?format=ndjson",
+    "GET /api/groups/{tag}/status",
+    "GET /api/versions",
+    "GET /api/network/players?range=week",
+    "GET /api/features",
+    "GET /api/metrics",
+];
+
+#[derive(Serialize)]
+struct ApiIndex {
+    name: &'static str,
+    version: &'static str,
+    api_base: &'static str,
+    endpoints: &'static [&'static str],
+    login: &'static str,
+}
+
+// API_ONLY replacement for the frontend's index page
+async fn api_index() -> Json<ApiIndex> {
+    Json(ApiIndex {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        api_base: "/api",
+        endpoints: API_INDEX,
+        login: "POST /auth/login (form: username, password), admin endpoints need the session cookie",
+    })
+}
+
+async fn api_not_found() -> ApiError {
+    StatusCode::NOT_FOUND.into()
+}
+
+// --- MIDDLEWARE ---
+
+// Aborts API requests that run longer than REQUEST_TIMEOUT_SECS with a 504
+async fn request_timeout(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    match tokio::time::timeout(state.config.request_timeout, next.run(req)).await {
+        Ok(res) => res,
+        Err(_) => ApiError::new(StatusCode::GATEWAY_TIMEOUT, "request timed out").into_response(),
+    }
+}
+
+// Rejects clients outside ADMIN_IP_ALLOWLIST with a 403 (no-op when the list is empty)
+async fn admin_ip_allowlist(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<PeerAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let allowlist = &state.config.admin_ip_allowlist;
+    if !allowlist.is_empty() {
+        // Fail closed when the client address can't be determined
+        let ip = net::client_ip(req.headers(), peer, state.config.trust_proxy);
+        if !ip.is_some_and(|ip| allowlist.iter().any(|net| net.contains(ip))) {
+            return ApiError::new(
+                StatusCode::FORBIDDEN,
+                "admin access is not allowed from this address",
+            )
+            .into_response();
+        }
+    }
+    next.run(req).await
+}
+
+fn init_logging(format: LogFormat) {
+    // No color codes when stdout goes to a file or a log collector
+    let logs = tracing_subscriber::fmt()
+        .with_target(false)
+        .with_ansi(std::io::stdout().is_terminal());
+    match format {
+        LogFormat::Text => logs.init(),
+        // One object per line with the fields at the top level, as Loki/ELK expect
+        LogFormat::Json => logs.json().flatten_event(true).init(),
+    }
+}
+
+// One log line per request. The id is echoed in `X-Request-Id` and in JSON error bodies so
+// a client report can be matched to its log line; anything logged while handling the
+// request carries it too.
+async fn log_requests(req: Request, next: Next) -> Response {
+    let request_id = new_request_id();
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let started = Instant::now();
+
+    let span = info_span!("request", request_id = %request_id);
+    let mut res = REQUEST_ID
+        .scope(request_id.clone(), next.run(req).instrument(span))
+        .await;
+
+    info!(
+        request_id,
+        method = %method,
+        path,
+        status = res.status().as_u16(),
+        latency_ms = started.elapsed().as_micros() as f64 / 1000.0,
+        "request"
+    );
+    if let Ok(value) = header::HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert("x-request-id", value);
+    }
+    res
+}
+
+// Random (version 4) UUID
+fn new_request_id() -> String {
+    let mut b = [0u8; 16];
+    OsRng.fill_bytes(&mut b);
+    b[6] = (b[6] & 0x0f) | 0x40;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    let h = hex::encode(b);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &h[..8],
+        &h[8..12],
+        &h[12..16],
+        &h[16..20],
+        &h[20..]
+    )
+}
+
+// To visitors a private server simply isn't there
+async fn hide_private_servers(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    req: Request,
+    next: Next,
+) -> Response {
+    match state.db.get_server_by_id(id).await {
+        Ok(Some(s)) if !s.is_public && !is_admin(&state, req.headers()).await => {
+            ApiError::from(StatusCode::NOT_FOUND).into_response()
+        }
+        _ => next.run(req).await,
+    }
+}
+
+// With AUTH_REQUIRED_FOR_READS, the public endpoints need a session like the admin ones
+async fn auth_required_for_reads(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if state.config.auth_required_for_reads && !is_admin(&state, req.headers()).await {
+        return ApiError::from(StatusCode::UNAUTHORIZED).into_response();
+    }
+    next.run(req).await
+}
+
+// Turns away admin and auth routes when READ_ONLY is set
+async fn read_only(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    if state.config.read_only {
+        return ApiError::new(StatusCode::FORBIDDEN, "this instance is read-only").into_response();
+    }
+    next.run(req).await
 }
 
 // --- HANDLERS ---
 
-async fn init_default_admin(db: &Database) {
+async fn init_default_admin(db: &Database, config: &Config) {
     let default_user = "admin";
     let default_pass = env::var("ADMIN_PASSWORD").unwrap_or_else(|_| "change_me".to_string());
 
@@ -174,46 +1380,74 @@ async fn init_default_admin(db: &Database) {
         return;
     }
 
-    let hash = hash_password(&default_pass);
-    if let Err(e) = db.ensure_admin_user(default_user, &hash).await {
-        eprintln!("Failed to create default admin: {:?}", e);
-    } else {
-        println!("Admin created: {}", default_user);
+    // Not fatal, the admin must be able to log in to change it
+    if let Err(msg) = config.password_policy.check(&default_pass) {
+        warn!("The default admin password is weak ({msg}).");
+        warn!("Set ADMIN_PASSWORD or change it with POST /api/auth/password after logging in.");
+    }
+    let hash = hash_password(config, &default_pass);
+    match db.ensure_admin_user(default_user, &hash).await {
+        Ok(true) => info!("Admin created: {}", default_user),
+        Ok(false) => {} // Another instance got there first
+        Err(e) => error!("Failed to create default admin: {:?}", e),
     }
 }
 
 // POST /auth/login
-async fn handle_login(State(state): State<AppState>, Form(form): Form<LoginForm>) -> Response {
+async fn handle_login(
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> Response {
     let maybe_admin = state
         .db
         .get_admin_by_username(&form.username)
         .await
         .ok()
         .flatten();
-    if let Some(admin) = maybe_admin {
-        if verify_password(&admin.password_hash, &form.password) {
-            let token = generate_session_token();
-            if state
-                .db
-                .create_admin_session(admin.id, &token)
-                .await
-                .is_ok()
-            {
-                let mut headers = HeaderMap::new();
-                let is_prod = env::var("APP_ENV").unwrap_or_default() == "production";
-                let secure = if is_prod { "; Secure" } else { "" };
-                let cookie = format!(
-                    "admin_session={}; HttpOnly; SameSite=Strict; Path=/{}{}",
-                    token, secure, ""
-                );
-                headers.insert(
-                    header::SET_COOKIE,
-                    header::HeaderValue::from_str(&cookie).unwrap(),
-                );
-
-                // Redirect back to home on success
-                return (headers, Redirect::to("/")).into_response();
+    if let Some(admin) = maybe_admin
+        && verify_password_blocking(&state.config, &admin.password_hash, &form.password).await
+    {
+        // Only now is the plain password at hand to upgrade a hash made with old settings
+        if needs_rehash(&state.config, &admin.password_hash) {
+            let hash = hash_password_blocking(&state.config, &form.password).await;
+            match state.db.update_admin_password_hash(admin.id, &hash).await {
+                Ok(()) => info!(
+                    "Rehashed password of '{}' with the current argon2 settings",
+                    admin.username
+                ),
+                Err(e) => error!("Failed to rehash password of '{}': {:?}", admin.username, e),
+            }
+        }
+        // Whatever session the browser brought is retired, a login always starts a new one
+        if let Some(old) = get_session_token_from_headers(&request_headers) {
+            let _ = state.db.delete_session(&old).await;
+        }
+        let token = generate_session_token();
+        if state
+            .db
+            .create_admin_session(admin.id, &token)
+            .await
+            .is_ok()
+        {
+            let keep = state.config.max_sessions_per_admin;
+            match state.db.prune_oldest_sessions(admin.id, keep).await {
+                Ok(0) => {}
+                Ok(n) => info!("Logged out {} old session(s) of '{}'", n, admin.username),
+                Err(e) => error!("Failed to prune sessions of '{}': {:?}", admin.username, e),
             }
+            let mut headers = HeaderMap::new();
+            // Browser drops it when the server-side session expires
+            let max_age = state.config.session_ttl.as_secs() as i64;
+            let cookie =
+                build_session_cookie(&state.config, &request_headers, &token, Some(max_age));
+            headers.insert(
+                header::SET_COOKIE,
+                header::HeaderValue::from_str(&cookie).unwrap(),
+            );
+
+            // Redirect back to home on success
+            return (headers, Redirect::to("/")).into_response();
         }
     }
     sleep(Duration::from_secs(2)).await;
@@ -222,22 +1456,117 @@ async fn handle_login(State(state): State<AppState>, Form(form): Form<LoginForm>
 }
 
 // GET /auth/logout
-async fn handle_logout(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
-    if let Some(token) = get_session_token_from_headers(&headers) {
+async fn handle_logout(
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Some(token) = get_session_token_from_headers(&request_headers) {
         let _ = state.db.delete_session(&token).await;
     }
     let mut headers = HeaderMap::new();
+    // Same attributes as at login, otherwise the browser treats it as a different cookie
+    let cookie = build_session_cookie(&state.config, &request_headers, "deleted", Some(0));
     headers.insert(
         header::SET_COOKIE,
-        header::HeaderValue::from_static(
-            "admin_session=deleted; HttpOnly; SameSite=Strict; Path=/; Max-Age=0",
-        ),
+        header::HeaderValue::from_str(&cookie).unwrap(),
     );
     (headers, Redirect::to("/"))
 }
 
+// Session cookie with the COOKIE_SAMESITE / COOKIE_DOMAIN / COOKIE_SECURE attributes applied
+fn build_session_cookie(
+    config: &Config,
+    request: &HeaderMap,
+    value: &str,
+    max_age: Option<i64>,
+) -> String {
+    let mut cookie = format!(
+        "admin_session={}; HttpOnly; SameSite={}; Path=/",
+        value,
+        config.cookie_same_site.as_str()
+    );
+    let secure = match config.cookie_secure {
+        CookieSecure::Always => true,
+        CookieSecure::Never => false,
+        CookieSecure::Auto => net::forwarded_https(request, config.trust_proxy),
+    };
+    if secure {
+        cookie.push_str("; Secure");
+    }
+    if let Some(domain) = &config.cookie_domain {
+        cookie.push_str(&format!("; Domain={}", domain));
+    }
+    if let Some(age) = max_age {
+        cookie.push_str(&format!("; Max-Age={}", age));
+    }
+    cookie
+}
+
 // API Handlers (JSON)
 
+async fn health(State(state): State<AppState>) -> Result<Json<HealthResponse>, StatusCode> {
+    let wal_size_bytes = state
+        .db
+        .wal_size_bytes()
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok(Json(HealthResponse {
+        status: "ok",
+        wal_size_bytes,
+        pools: state.db.pool_stats(),
+    }))
+}
+
+async fn stats(State(state): State<AppState>) -> Result<Json<StatsResponse>, ApiError> {
+    let now = now_epoch();
+    let last_ping_age_secs = state.db.latest_ping_epoch().await?.map(|t| now - t);
+    let cycles = state.ping_cycles.lock().unwrap();
+    let avg_cycle_secs = (!cycles.recent.is_empty()).then(|| {
+        cycles.recent.iter().map(Duration::as_secs_f64).sum::<f64>() / cycles.recent.len() as f64
+    });
+    let healthy_since = cycles.last_completed_at.unwrap_or(state.started_at);
+    Ok(Json(StatsResponse {
+        ping_interval_secs: PING_INTERVAL_SECS,
+        ping_cycles: cycles.completed,
+        last_cycle_servers: cycles.last_servers,
+        last_cycle_secs: cycles.recent.back().map(Duration::as_secs_f64),
+        avg_cycle_secs,
+        last_cycle_at: cycles.last_completed_at.map(epoch_to_rfc3339),
+        last_ping_age_secs,
+        pinger_healthy: now - healthy_since <= state.config.stale_after_secs,
+    }))
+}
+
+// Private servers aren't counted for visitors, same as in the list
+async fn instance_info(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<InfoResponse>, ApiError> {
+    let server_count = state
+        .db
+        .count_servers(is_admin(&state, &headers).await)
+        .await?;
+    Ok(Json(InfoResponse {
+        name: state.config.instance_name.clone(),
+        version: env!("CARGO_PKG_VERSION"),
+        server_count,
+    }))
+}
+
+// GET /api/features
+async fn features(State(state): State<AppState>) -> Json<FeaturesResponse> {
+    let config = &state.config;
+    Json(FeaturesResponse {
+        login: !config.read_only,
+        auth_required_for_reads: config.auth_required_for_reads,
+        webhooks: config.webhook_url.is_some(),
+        metrics: true,
+        cors: !config.cors_origins.is_empty(),
+        ip_allowlist: !config.admin_ip_allowlist.is_empty(),
+        collapse_repeated_offline: config.collapse_repeated_offline,
+    })
+}
+
 async fn auth_me(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -245,7 +1574,7 @@ async fn auth_me(
     let token = get_session_token_from_headers(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
     let admin = state
         .db
-        .get_admin_by_session_token(&token)
+        .get_admin_by_session_token(&token, state.config.session_ttl.as_secs() as i64)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -256,17 +1585,80 @@ async fn auth_me(
     }
 }
 
-#[derive(Debug, Serialize)]
+// POST /api/auth/password
+async fn change_password(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<ChangePasswordJson>,
+) -> Result<Json<SimpleResponse>, ApiError> {
+    let admin = get_admin_from_headers(&state, &headers).await?;
+    if !verify_password_blocking(&state.config, &admin.password_hash, &body.current_password).await
+    {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "current password is wrong",
+        ));
+    }
+    state
+        .config
+        .password_policy
+        .check(&body.new_password)
+        .map_err(|msg| ApiError::new(StatusCode::BAD_REQUEST, msg))?;
+
+    let hash = hash_password_blocking(&state.config, &body.new_password).await;
+    state.db.update_admin_password_hash(admin.id, &hash).await?;
+    Ok(Json(SimpleResponse { success: true }))
+}
+
+/// Current state from the newest ping, `Unknown` while a server hasn't been pinged yet.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ServerStatus {
+    Online,
+    Offline,
+    Unknown,
+}
+
+impl ServerStatus {
+    fn of(last: Option<&PingResult>) -> Self {
+        match last {
+            Some(p) if p.online => ServerStatus::Online,
+            Some(_) => ServerStatus::Offline,
+            None => ServerStatus::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 struct LightServerApi {
     pub id: i64,
     pub name: String,
     pub address: String,
+    // Also false before the first ping, `status` tells the two apart
     pub last_online: bool,
+    pub status: ServerStatus,
+    pub color: Option<String>,
+    pub is_public: bool,
+    pub is_pinned: bool,
+    // As of when the list was built, so up to SERVERS_CACHE_SECS behind
+    pub last_ping_age_secs: Option<i64>,
 }
 
 async fn list_servers(
     State(state): State<AppState>,
-) -> Result<Json<Vec<LightServerApi>>, StatusCode> {
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let ttl = state.config.servers_cache_ttl;
+    let admin = is_admin(&state, &headers).await;
+
+    // Serve from cache while it's fresh
+    if let Some(cached) = state.servers_cache.lock().unwrap().as_ref() {
+        let age = cached.built_at.elapsed();
+        if age < ttl {
+            return Ok(servers_response(&cached.servers, admin, age, ttl));
+        }
+    }
+
     let servers = state
         .db
         .list_servers()
@@ -283,9 +1675,93 @@ async fn list_servers(
             id: s.id,
             name: s.name,
             address: s.address,
-            last_online: last.map(|p| p.online).unwrap_or(false),
+            last_online: last.as_ref().is_some_and(|p| p.online),
+            status: ServerStatus::of(last.as_ref()),
+            last_ping_age_secs: last.map(|p| now_epoch() - p.pinged_at_epoch),
+            color: s.color,
+            is_public: s.is_public,
+            is_pinned: s.is_pinned,
+        });
+    }
+
+    if !ttl.is_zero() {
+        *state.servers_cache.lock().unwrap() = Some(CachedServers {
+            built_at: Instant::now(),
+            servers: res.clone(),
         });
     }
+    Ok(servers_response(&res, admin, Duration::ZERO, ttl))
+}
+
+// The cache holds every server, private ones are dropped here for visitors
+fn servers_response(
+    servers: &[LightServerApi],
+    admin: bool,
+    age: Duration,
+    ttl: Duration,
+) -> Response {
+    let max_age = ttl.saturating_sub(age).as_secs();
+    // An admin's list includes private servers, keep it out of shared caches
+    let scope = if admin { "private" } else { "public" };
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_str(&format!("{}, max-age={}", scope, max_age)).unwrap(),
+    );
+    headers.insert(header::AGE, header::HeaderValue::from(age.as_secs()));
+    let visible: Vec<&LightServerApi> = servers.iter().filter(|s| admin || s.is_public).collect();
+    (headers, Json(visible)).into_response()
+}
+
+// GET /api/servers/search?q=surv
+async fn search_servers(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<ServerApi>>, ApiError> {
+    const MAX_RESULTS: i64 = 50;
+    let q = params.q.trim();
+    if q.is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "q is required"));
+    }
+
+    let admin = is_admin(&state, &headers).await;
+    let servers = state.db.search_servers(q, MAX_RESULTS, admin).await?;
+    let mut res = Vec::with_capacity(servers.len());
+    for s in servers {
+        let last = state.db.get_last_ping_for_server(s.id).await?;
+        res.push(ServerApi::from_server(s, last.as_ref()));
+    }
+    Ok(Json(res))
+}
+
+async fn list_offline_servers(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<OfflineParams>,
+) -> Result<Json<Vec<OfflineServerApi>>, StatusCode> {
+    let admin = is_admin(&state, &headers).await;
+    let servers = state
+        .db
+        .list_offline_servers()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let now = now_epoch();
+    let grace = params.grace_secs.unwrap_or(0);
+
+    let res = servers
+        .into_iter()
+        .filter(|s| admin || s.is_public)
+        .map(|s| OfflineServerApi {
+            id: s.id,
+            name: s.name,
+            address: s.address,
+            port: s.port,
+            down_since: epoch_to_rfc3339(s.down_since_epoch),
+            down_secs: (now - s.down_since_epoch).max(0),
+        })
+        .filter(|s| s.down_secs >= grace)
+        .collect();
     Ok(Json(res))
 }
 
@@ -293,27 +1769,208 @@ async fn create_server_json(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(body): Json<CreateServerJson>,
-) -> Result<Json<ServerApi>, StatusCode> {
+) -> Result<Json<ServerApi>, ApiError> {
     let _ = get_admin_from_headers(&state, &headers).await?;
-    if body.port.unwrap_or(25565) < 1 || body.name.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+    // There's no usual port for an arbitrary TCP service
+    let port = match (body.port, body.check_type) {
+        (Some(port), _) => port,
+        (None, CheckType::Minecraft) => body.edition.default_port(),
+        (None, CheckType::Tcp) => {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "port is required for tcp checks",
+            ));
+        }
+    };
+    if !(1..=65535).contains(&port) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "port must be between 1 and 65535",
+        ));
+    }
+    if body.name.is_empty() {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    validate_host(&body.address).map_err(|msg| ApiError::new(StatusCode::BAD_REQUEST, msg))?;
+    let settings = ServerUpdate {
+        name: None,
+        enable_query: body.enable_query,
+        query_port: body.query_port.map(Some),
+        protocol_hint: body.protocol_hint.map(Some),
+        modded: body.modded,
+        tags: body.tags,
+        edition: None,
+        check_type: Some(body.check_type),
+        color: body.color.map(Some),
+        is_public: body.is_public,
+        webhook_url: body.webhook_url.map(Some),
+        player_alert_threshold: body.player_alert_threshold.map(Some),
+    };
+    validate_server_update(&settings).map_err(|msg| ApiError::new(StatusCode::BAD_REQUEST, msg))?;
+    ensure_server_capacity(&state, 1).await?;
+    if !body.allow_duplicate {
+        ensure_unique_endpoint(&state, &body.address, port).await?;
     }
 
     let id = state
         .db
-        .insert_server(&body.name, &body.address, body.port.unwrap_or(25565))
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .insert_server(&body.name, &body.address, port, body.edition)
+        .await?;
+    state.db.update_server(id, &settings).await?;
+    state.invalidate_servers_cache();
     let s = state.db.get_server_by_id(id).await.unwrap().unwrap();
 
-    Ok(Json(ServerApi {
-        id: s.id,
-        name: s.name,
-        address: s.address,
-        port: s.port,
-        created_at: s.created_at,
-        last_online: false,
-    }))
+    Ok(Json(ServerApi::from_server(s, None)))
+}
+
+async fn update_server(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Json(body): Json<ServerUpdate>,
+) -> Result<Json<ServerApi>, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    validate_server_update(&body).map_err(|msg| ApiError::new(StatusCode::BAD_REQUEST, msg))?;
+
+    let found = state.db.update_server(id, &body).await?;
+    if !found {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    state.invalidate_servers_cache();
+
+    let s = state
+        .db
+        .get_server_by_id(id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let last = state.db.get_last_ping_for_server(id).await.unwrap_or(None);
+    Ok(Json(ServerApi::from_server(s, last.as_ref())))
+}
+
+fn validate_server_update(u: &ServerUpdate) -> Result<(), &'static str> {
+    if u.name.as_ref().is_some_and(|n| n.is_empty()) {
+        return Err("name can't be empty");
+    }
+    if let Some(Some(port)) = u.query_port
+        && !(1..=65535).contains(&port)
+    {
+        return Err("query_port must be between 1 and 65535");
+    }
+    if let Some(Some(protocol)) = u.protocol_hint
+        && !(0..=i32::MAX as i64).contains(&protocol)
+    {
+        return Err("protocol_hint must be a non-negative protocol number");
+    }
+    if let Some(tags) = &u.tags {
+        if tags.len() > MAX_TAGS_PER_SERVER {
+            return Err("too many tags (max 20)");
+        }
+        if !tags.iter().all(|t| is_valid_tag(&normalize_tag(t))) {
+            return Err("tags must be 1-32 characters of a-z, 0-9, '-', '_' or '.'");
+        }
+    }
+    if let Some(Some(color)) = &u.color
+        && !is_valid_color(color)
+    {
+        return Err("color must be a hex color like #1e90ff or #f80");
+    }
+    if let Some(Some(url)) = &u.webhook_url {
+        webhook::validate_url(url)?;
+    }
+    if let Some(Some(threshold)) = u.player_alert_threshold
+        && threshold < 1
+    {
+        return Err("player_alert_threshold must be at least 1");
+    }
+    Ok(())
+}
+
+const MAX_TAGS_PER_SERVER: usize = 20;
+
+// Tags end up in URLs (/api/groups/{tag}) and a comma separated column, so keep them plain
+fn is_valid_tag(tag: &str) -> bool {
+    (1..=32).contains(&tag.len())
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Rejects addresses that can't possibly be pinged: URLs, `host:port`, whitespace and
+/// characters that aren't allowed in hostnames. IPv4 and (bare, unbracketed) IPv6
+/// literals are accepted as is. Checks the normalized form that ends up stored.
+fn validate_host(address: &str) -> Result<(), &'static str> {
+    let host = normalize_address(address);
+    if host.is_empty() {
+        return Err("address can't be empty");
+    }
+    if host.contains("://") {
+        return Err(
+            "address must be a bare host like play.example.com, without http:// or similar",
+        );
+    }
+    if host.chars().any(char::is_whitespace) {
+        return Err("address can't contain whitespace");
+    }
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        return Ok(());
+    }
+    if host.contains(':') || host.contains('/') {
+        return Err("address must not include a port or path, set the port separately");
+    }
+    // '_' isn't strictly valid in hostnames but does occur in real DNS names
+    let valid_label = |l: &str| {
+        (1..=63).contains(&l.len())
+            && !l.starts_with('-')
+            && !l.ends_with('-')
+            && l.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    if host.len() > 253 || !host.split('.').all(valid_label) {
+        return Err("address is not a valid hostname or IP address");
+    }
+    Ok(())
+}
+
+// Only "#rgb" / "#rrggbb": the value goes straight into CSS on the dashboard
+fn is_valid_color(color: &str) -> bool {
+    let Some(hex) = color.trim().strip_prefix('#') else {
+        return false;
+    };
+    matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// Rejects adding `adding` more servers when that would exceed MAX_SERVERS
+async fn ensure_server_capacity(state: &AppState, adding: i64) -> Result<(), ApiError> {
+    let Some(max) = state.config.max_servers else {
+        return Ok(());
+    };
+    let count = state.db.count_servers(true).await?;
+    if count + adding > max {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!("server limit reached ({} of {} allowed)", count, max),
+        ));
+    }
+    Ok(())
+}
+
+// Rejects an address:port that's already tracked, pinging it twice is pointless
+async fn ensure_unique_endpoint(
+    state: &AppState,
+    address: &str,
+    port: i64,
+) -> Result<(), ApiError> {
+    let existing = state.db.find_server_by_endpoint(address, port).await?;
+    if let Some(s) = existing {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!(
+                "{}:{} is already tracked as \"{}\" (id {})",
+                address, port, s.name, s.id
+            ),
+        ));
+    }
+    Ok(())
 }
 
 async fn delete_server(
@@ -327,112 +1984,1627 @@ async fn delete_server(
         .delete_server(id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    state.forget_ping_slots(&[id]);
+    state.invalidate_servers_cache();
     Ok(Json(SimpleResponse { success: true }))
 }
 
-async fn ping_and_store(
+async fn reorder_servers(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Path(id): Path<i64>,
-) -> Result<Json<SimpleResponse>, StatusCode> {
+    Json(body): Json<ReorderServersJson>,
+) -> Result<Json<SimpleResponse>, ApiError> {
     let _ = get_admin_from_headers(&state, &headers).await?;
-    ping_one_server(&state, id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut unique = body.ids.clone();
+    unique.sort_unstable();
+    unique.dedup();
+    if unique.len() != body.ids.len() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "ids must not contain duplicates",
+        ));
+    }
+
+    if !state.db.reorder_servers(&body.ids).await? {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "ids contains a server that doesn't exist",
+        ));
+    }
+    state.invalidate_servers_cache();
     Ok(Json(SimpleResponse { success: true }))
 }
 
-async fn list_server_ping_history(
+// Upper bound on ids accepted by one bulk delete
+const MAX_BULK_DELETE: usize = 1000;
+
+async fn delete_servers(
     State(state): State<AppState>,
-    Path(id): Path<i64>,
-    Query(params): Query<HistoryParams>,
-) -> Result<Json<Vec<PingResult>>, StatusCode> {
-    // 1. Determine time window
-    let seconds = match params.range.as_deref() {
-        Some("week") => Some(60 * 60 * 24 * 7),
-        Some("month") => Some(60 * 60 * 24 * 30),
-        Some("day") | _ => Some(60 * 60 * 24), // default to day
-    };
+    headers: HeaderMap,
+    body: Result<Json<DeleteServersJson>, JsonRejection>,
+) -> Result<Json<DeleteServersResponse>, ApiError> {
+    // Auth before looking at the body, a bad body from an anonymous caller is still a 401
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let Json(mut body) = body.map_err(|e| ApiError::new(e.status(), e.body_text()))?;
+    if body.ids.len() > MAX_BULK_DELETE {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("at most {} ids per request", MAX_BULK_DELETE),
+        ));
+    }
+    body.ids.sort_unstable();
+    body.ids.dedup();
 
-    // If asking for incremental updates (since_id), ignore the time window
-    let window = if params.since_id.is_some() {
-        None
-    } else {
-        seconds
-    };
+    let deleted = state.db.delete_servers(&body.ids).await?;
+    if !deleted.is_empty() {
+        state.forget_ping_slots(&deleted);
+        state.invalidate_servers_cache();
+    }
+    let not_found = body
+        .ids
+        .into_iter()
+        .filter(|id| !deleted.contains(id))
+        .collect();
+    Ok(Json(DeleteServersResponse {
+        deleted: deleted.len(),
+        not_found,
+    }))
+}
 
-    let raw_pings = state
+async fn migrate_server(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Json(body): Json<MigrateServerJson>,
+) -> Result<Json<ServerApi>, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let current = state
         .db
-        .get_pings_subset(id, params.since_id, window)
+        .get_server_by_id(id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let port = body.port.unwrap_or(current.edition.default_port());
+    if !(1..=65535).contains(&port) {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    validate_host(&body.address).map_err(|msg| ApiError::new(StatusCode::BAD_REQUEST, msg))?;
 
-    // For small result sets or incremental updates, just return raw
-    let should_optimize = params.since_id.is_none()
+    state
+        .db
+        .migrate_server(id, &body.address, port)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    state.invalidate_servers_cache();
+
+    if body.marker_ping.unwrap_or(false) {
+        let _ = ping_one_server(&state, id, PingSource::Manual).await;
+    }
+
+    let s = state
+        .db
+        .get_server_by_id(id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let last = state.db.get_last_ping_for_server(id).await.unwrap_or(None);
+
+    Ok(Json(ServerApi::from_server(s, last.as_ref())))
+}
+
+// Upper bound on rows accepted by one bulk report
+const MAX_BULK_PINGS: usize = 5000;
+
+async fn bulk_insert_pings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Json(mut body): Json<BulkPingsJson>,
+) -> Result<Json<BulkPingsResponse>, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    if body.pings.len() > MAX_BULK_PINGS {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("at most {} pings per request", MAX_BULK_PINGS),
+        ));
+    }
+    if state.db.get_server_by_id(id).await?.is_none() {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+
+    for (i, p) in body.pings.iter_mut().enumerate() {
+        p.server_id = id;
+        p.source = PingSource::Agent;
+        validate_new_ping(p).map_err(|msg| {
+            ApiError::new(StatusCode::BAD_REQUEST, format!("ping {}: {}", i, msg))
+        })?;
+    }
+
+    let inserted = state.db.insert_ping_results_batch(&body.pings).await?;
+    state.invalidate_servers_cache();
+
+    Ok(Json(BulkPingsResponse { inserted }))
+}
+
+// Checks a reported ping and normalizes its timestamp to the stored format
+fn validate_new_ping(p: &mut NewPing) -> Result<(), &'static str> {
+    if let Some(t) = &p.pinged_at {
+        let parsed =
+            chrono::DateTime::parse_from_rfc3339(t).map_err(|_| "pinged_at must be RFC 3339")?;
+        p.pinged_at = Some(
+            parsed
+                .with_timezone(&chrono::Utc)
+                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        );
+    }
+    if p.players_online.is_some_and(|n| n < 0) || p.players_max.is_some_and(|n| n < 0) {
+        return Err("player counts can't be negative");
+    }
+    if p.latency_ms.is_some_and(|n| n < 0) {
+        return Err("latency_ms can't be negative");
+    }
+    if !p.online && (p.players_online.is_some() || p.version.is_some()) {
+        return Err("offline pings can't carry status data");
+    }
+    Ok(())
+}
+
+async fn server_query_data(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let (pinged_at, data) = state
+        .db
+        .get_last_query_data(id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mut value: serde_json::Value =
+        serde_json::from_str(&data).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    value["pinged_at"] = pinged_at.into();
+    Ok(Json(value))
+}
+
+#[derive(Serialize)]
+struct ModsResponse {
+    pinged_at: String,
+    mods: serde_json::Value,
+}
+
+// GET /api/servers/{id}/mods -> mod list of the last ping that reported one (Forge servers)
+async fn server_mods(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<ModsResponse>, ApiError> {
+    let (pinged_at, mods) = state
+        .db
+        .get_last_mods(id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mods = serde_json::from_str(&mods).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(ModsResponse { pinged_at, mods }))
+}
+
+#[derive(Serialize)]
+struct PlayersResponse {
+    pinged_at: String,
+    players: Vec<players::SamplePlayer>,
+}
+
+// GET /api/servers/{id}/players -> player sample of the last answered ping, cleaned up.
+// Servers only send a sample (usually up to 12 names), not necessarily everyone online
+async fn server_players(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<PlayersResponse>, ApiError> {
+    let (pinged_at, sample) = state
+        .db
+        .get_last_player_sample(id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let sample = serde_json::from_str(&sample).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let players = players::clean_sample(sample, state.config.hide_placeholder_players);
+    Ok(Json(PlayersResponse { pinged_at, players }))
+}
+
+// GET /api/admin/config -> effective settings after env parsing, secrets redacted
+async fn admin_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let admin_password = if env::var("ADMIN_PASSWORD").is_ok() {
+        "***"
+    } else {
+        "(default)"
+    };
+    Ok(Json(AdminConfigResponse {
+        config: &state.config,
+        ping_interval_secs: PING_INTERVAL_SECS,
+        ping_retention_days: PING_RETENTION_DAYS,
+        admin_password,
+        webhook_url: state.config.webhook_url.as_ref().map(|_| "***"),
+    })
+    .into_response())
+}
+
+// GET /api/admin/storage -> how big the database is and where it's heading
+async fn admin_storage(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<StorageResponse>, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let size_bytes = state.db.size_bytes().await?;
+    let wal_size_bytes = state.db.wal_size_bytes().await?;
+    let tables: BTreeMap<String, i64> = state.db.table_row_counts().await?.into_iter().collect();
+
+    let now = now_epoch();
+    let (recent, oldest) = state
+        .db
+        .count_pings_since(now - STORAGE_RATE_DAYS * 24 * 60 * 60)
+        .await?;
+    // A young database hasn't filled the window yet, measure over what it has
+    let span = oldest.map_or(0, |t| now - t).max(PING_INTERVAL_SECS as i64);
+    let ping_rows_per_day = recent as f64 * (24 * 60 * 60) as f64 / span as f64;
+
+    let total_rows: i64 = tables.values().sum();
+    let bytes_per_row = (total_rows > 0).then(|| size_bytes as f64 / total_rows as f64);
+    let ping_rows = tables.get("ping_results").copied().unwrap_or(0);
+    let steady_rows = ping_rows_per_day * PING_RETENTION_DAYS as f64;
+    let projected =
+        size_bytes as f64 + (steady_rows - ping_rows as f64) * bytes_per_row.unwrap_or(0.0);
+    // Deleted rows leave free pages behind, the file never shrinks without VACUUM
+    let projected_size_bytes = (projected as u64).max(size_bytes);
+
+    Ok(Json(StorageResponse {
+        size_bytes,
+        wal_size_bytes,
+        tables,
+        ping_rows_per_day,
+        bytes_per_row,
+        ping_retention_days: PING_RETENTION_DAYS,
+        projected_size_bytes,
+    }))
+}
+
+// Pings buffered between the database cursor and the client. When a slow client lets it
+// fill up, reading from the database pauses until it catches up.
+const EXPORT_BUFFER_PINGS: usize = 256;
+
+// Full ping history as NDJSON, one PingResult per line, streamed straight off the cursor
+async fn export_server_history(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<ExportParams>,
+) -> Result<Response, ApiError> {
+    if params.format.as_deref().unwrap_or("ndjson") != "ndjson" {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "format must be ndjson",
+        ));
+    }
+    if state.db.get_server_by_id(id).await?.is_none() {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+
+    let (tx, rx) = mpsc::channel(EXPORT_BUFFER_PINGS);
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        if let Err(e) = db.send_pings(id, tx).await {
+            error!("Export of server {} failed: {:?}", id, e);
+        }
+    });
+    // Dropping the body (client gone) drops rx, which stops the cursor
+    let lines = futures_util::stream::unfold(rx, |mut rx| async move {
+        let ping = rx.recv().await?;
+        let mut line = serde_json::to_vec(&ping).unwrap_or_default();
+        line.push(b'\n');
+        Some((Ok::<_, std::convert::Infallible>(line), rx))
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/x-ndjson"),
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        header::HeaderValue::from_str(&format!(
+            "attachment; filename=\"server-{}-pings.ndjson\"",
+            id
+        ))
+        .unwrap(),
+    );
+    Ok((headers, Body::from_stream(lines)).into_response())
+}
+
+async fn download_backup(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+
+    let path = env::temp_dir().join(format!("web-server-backup-{}.db", generate_session_token()));
+    let path_str = path.to_string_lossy().to_string();
+    // Removes the copy on every exit, including the request timeout dropping this future
+    let temp_file = RemoveOnDrop(path.clone());
+    if let Err(e) = state.db.backup_to(&path_str).await {
+        error!("Backup failed: {:?}", e);
+        return Err(ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "backup failed",
+        ));
+    }
+
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let len = file.metadata().await.map(|m| m.len()).ok();
+    // The open handle keeps the data readable on unix, so the temp file can go right away
+    // instead of lingering if the client disconnects mid-download.
+    drop(temp_file);
+
+    let filename = format!(
+        "web-server-backup-{}.db",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    );
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/octet-stream"),
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        header::HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename)).unwrap(),
+    );
+    if let Some(len) = len {
+        headers.insert(header::CONTENT_LENGTH, header::HeaderValue::from(len));
+    }
+
+    let body = Body::from_stream(ReaderStream::new(file));
+    Ok((headers, body).into_response())
+}
+
+// Deletes a temporary file when dropped
+struct RemoveOnDrop(std::path::PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            error!("Failed to remove temp file {}: {:?}", self.0.display(), e);
+        }
+    }
+}
+
+// GET /api/admin/servers/export -> server definitions without history, for restore.
+// The file includes webhook URLs, which are credentials; keep it somewhere private.
+async fn export_servers(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let servers = state
+        .db
+        .list_servers()
+        .await?
+        .into_iter()
+        .map(ServerDefinition::from_server)
+        .collect();
+    let now = chrono::Utc::now();
+    let snapshot = ServersSnapshot {
+        exported_at: now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        servers,
+    };
+    let filename = format!("web-server-servers-{}.json", now.format("%Y%m%d-%H%M%S"));
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        header::HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename)).unwrap(),
+    );
+    Ok((headers, Json(snapshot)).into_response())
+}
+
+// POST /api/admin/servers/restore -> recreates the servers of an export. Everything is
+// validated before the first insert; endpoints that are already tracked are skipped.
+async fn restore_servers(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<ServersSnapshot>,
+) -> Result<Json<RestoreServersResponse>, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let mut seen = std::collections::HashSet::new();
+    let mut pending = Vec::new();
+    let mut skipped = Vec::new();
+    for (i, def) in body.servers.iter().enumerate() {
+        let invalid =
+            |msg: &str| ApiError::new(StatusCode::BAD_REQUEST, format!("servers[{}]: {}", i, msg));
+        if def.name.is_empty() {
+            return Err(invalid("name can't be empty"));
+        }
+        if !(1..=65535).contains(&def.port) {
+            return Err(invalid("port must be between 1 and 65535"));
+        }
+        validate_host(&def.address).map_err(invalid)?;
+        validate_server_update(&def.settings()).map_err(invalid)?;
+
+        let endpoint = format!("{}:{}", normalize_address(&def.address), def.port);
+        let tracked = state
+            .db
+            .find_server_by_endpoint(&def.address, def.port)
+            .await?
+            .is_some();
+        if tracked || !seen.insert(endpoint.clone()) {
+            skipped.push(endpoint);
+        } else {
+            pending.push(def);
+        }
+    }
+    ensure_server_capacity(&state, pending.len() as i64).await?;
+
+    let restored: Vec<RestoredServer> = pending
+        .into_iter()
+        .map(|def| RestoredServer {
+            name: def.name.clone(),
+            address: def.address.clone(),
+            port: def.port,
+            edition: def.edition,
+            is_pinned: def.is_pinned,
+            settings: def.settings(),
+        })
+        .collect();
+    // All or nothing, a failure halfway doesn't leave a partial restore behind
+    let created = state.db.restore_servers(&restored).await?;
+    state.invalidate_servers_cache();
+    info!(
+        "Restored {} servers ({} already tracked)",
+        created.len(),
+        skipped.len()
+    );
+    Ok(Json(RestoreServersResponse { created, skipped }))
+}
+
+async fn list_server_events(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<ServerEvent>>, StatusCode> {
+    let events = state
+        .db
+        .list_server_events(id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(events))
+}
+
+// `?wait=true` answers with the stored ping instead of just `{ success }`
+async fn ping_and_store(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Query(params): Query<PingNowParams>,
+) -> Result<Response, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let inserted = ping_one_server(&state, id, PingSource::Manual)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !params.wait {
+        return Ok(Json(SimpleResponse { success: true }).into_response());
+    }
+
+    // No row means no such server (or a failed write). A ping folded into a collapsed
+    // outage (ours overlapped a scheduled one) still names the row it went into.
+    let Some(ping_id) = inserted else {
+        if state.db.get_server_by_id(id).await?.is_none() {
+            return Err(StatusCode::NOT_FOUND.into());
+        }
+        return Err(ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "the ping result could not be stored",
+        ));
+    };
+    let ping = state
+        .db
+        .get_ping_by_id(ping_id)
+        .await?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(ping).into_response())
+}
+
+async fn list_server_ping_history(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<PingResult>>, StatusCode> {
+    // 1. Determine time window
+    // Also applied to incremental updates (since_id), so a poll after a long pause
+    // or with a stale id can't return rows from outside the range on screen
+    let window = Some(range_seconds(params.range.as_deref()));
+
+    let raw_pings = state
+        .db
+        .get_pings_subset(id, params.since_id, window)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Outage-focused view: only the pings where the state flipped
+    if params.only_transitions.unwrap_or(false) {
+        return Ok(Json(transition_points(&raw_pings)));
+    }
+
+    // For small result sets or incremental updates, just return raw
+    let should_optimize = params.since_id.is_none()
         && (params.range.as_deref() == Some("month") || params.range.as_deref() == Some("week"));
 
-    if !should_optimize {
-        return Ok(Json(raw_pings));
+    if !should_optimize {
+        return Ok(Json(raw_pings));
+    }
+
+    if raw_pings.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    // 2. Downsampling aggressiveness
+    // per_chunk_secs = how coarse we compress long online segments
+    let per_chunk_secs: i64 = match params.range.as_deref() {
+        Some("month") => 6 * 60 * 60, // 6h chunks -> ~4 points per day -> 116-128
+        Some("week") => 60 * 60,      // 1h chunks -> ~24 points per day -> 168 per week
+        _ => 15 * 60,
+    };
+
+    // Treat any segment shorter than this as a "blip"
+    let short_blip_secs: i64 = 20 * 60; // 20 minutes
+
+    // TODO:
+    // Fix this to actually do what i want
+
+    // 3. Split into segments where online/offline remains constant
+    // Done on the blocking pool so a month of pings can't stall the runtime
+    // and the request timeout can still fire while it runs.
+    let max_points = state.config.max_history_points;
+    let optimized = tokio::task::spawn_blocking(move || {
+        decimate(
+            downsample(&raw_pings, per_chunk_secs, short_blip_secs),
+            max_points,
+        )
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(optimized))
+}
+
+// Longest mute accepted by /mute (30 days)
+const MAX_MUTE_MINUTES: i64 = 30 * 24 * 60;
+
+// POST /api/servers/{id}/mute?minutes=60
+// Silences alerts for a maintenance window, monitoring itself carries on
+async fn mute_server(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Query(params): Query<MuteParams>,
+) -> Result<Json<MuteResponse>, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let minutes = params.minutes.unwrap_or(60);
+    if !(0..=MAX_MUTE_MINUTES).contains(&minutes) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("minutes must be between 0 and {MAX_MUTE_MINUTES}"),
+        ));
+    }
+    let until = (minutes > 0).then(|| now_epoch() + minutes * 60);
+    if !state.db.set_muted_until(id, until).await? {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let detail = match until {
+        Some(t) => format!("until {}", epoch_to_rfc3339(t)),
+        None => "unmuted".to_string(),
+    };
+    let _ = state.db.insert_server_event(id, "muted", &detail).await;
+
+    Ok(Json(MuteResponse {
+        muted_until: until.map(epoch_to_rfc3339),
+    }))
+}
+
+// POST /api/servers/{id}/pin -> pins or unpins, pinned servers are listed first
+async fn toggle_pin(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<Json<PinResponse>, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let is_pinned = state
+        .db
+        .toggle_pinned(id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    state.invalidate_servers_cache();
+    Ok(Json(PinResponse { is_pinned }))
+}
+
+const MAX_LATENCY_SAMPLES: usize = 20;
+const LATENCY_SAMPLE_GAP: Duration = Duration::from_millis(100);
+
+// POST /api/servers/{id}/latency-test?count=10 -> back-to-back status pings, nothing stored
+async fn latency_test(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Query(params): Query<LatencyTestParams>,
+) -> Result<Json<LatencyTestResponse>, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let count = params.count.unwrap_or(10);
+    if !(1..=MAX_LATENCY_SAMPLES).contains(&count) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("count must be between 1 and {MAX_LATENCY_SAMPLES}"),
+        ));
+    }
+    let s = state
+        .db
+        .get_server_by_id(id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    // Resolved once, DNS time isn't part of what's measured
+    let addrs = resolve_endpoint(&s)
+        .await
+        .map_err(|e| ApiError::new(StatusCode::BAD_GATEWAY, format!("DNS lookup failed: {}", e)))?;
+
+    // One sample takes at most the connect plus the read timeout. No new sample is
+    // started unless it can finish in time, so a dead server can't run the request
+    // into REQUEST_TIMEOUT_SECS
+    let sample_max = state.config.ping_connect_timeout + state.config.ping_read_timeout;
+    let budget = state.config.request_timeout.saturating_sub(sample_max);
+    let started = Instant::now();
+    let mut samples_ms = Vec::with_capacity(count);
+    for i in 0..count {
+        if i > 0 {
+            if started.elapsed() + LATENCY_SAMPLE_GAP >= budget {
+                break;
+            }
+            tokio::time::sleep(LATENCY_SAMPLE_GAP).await;
+        }
+        let mut row = NewPing::default();
+        let sent = Instant::now();
+        ping_endpoint(&state, &s, &addrs, &mut row).await;
+        let ms = sent.elapsed().as_secs_f64() * 1000.0;
+        samples_ms.push(row.online.then_some(ms));
+    }
+
+    let ok: Vec<f64> = samples_ms.iter().flatten().copied().collect();
+    let jitter_ms = (ok.len() > 1)
+        .then(|| ok.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (ok.len() - 1) as f64);
+    Ok(Json(LatencyTestResponse {
+        min_ms: ok.iter().copied().reduce(f64::min),
+        avg_ms: (!ok.is_empty()).then(|| ok.iter().sum::<f64>() / ok.len() as f64),
+        max_ms: ok.iter().copied().reduce(f64::max),
+        jitter_ms,
+        samples_ms,
+    }))
+}
+
+// POST /api/servers/{id}/compact?older_than_days=30&chunk_hours=6
+// Permanently replaces old pings with the same downsampled points the month view shows.
+async fn compact_server_history(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Query(params): Query<CompactParams>,
+) -> Result<Json<CompactResponse>, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let days = params.older_than_days.unwrap_or(30);
+    let chunk_hours = params.chunk_hours.unwrap_or(6);
+    if !(1..=3650).contains(&days) || !(1..=24 * 7).contains(&chunk_hours) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "older_than_days must be between 1 and 3650 and chunk_hours between 1 and 168",
+        ));
+    }
+    state
+        .db
+        .get_server_by_id(id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let cutoff = now_epoch() - days * 24 * 60 * 60;
+    let old = state.db.get_pings_before(id, cutoff).await?;
+    if old.is_empty() {
+        return Ok(Json(CompactResponse {
+            deleted: 0,
+            inserted: 0,
+        }));
+    }
+
+    let points = tokio::task::spawn_blocking(move || {
+        downsample(&old, chunk_hours * 60 * 60, 20 * 60)
+            .iter()
+            .map(NewPing::compacted_from)
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let deleted = state.db.compact_pings(id, cutoff, &points).await?;
+
+    Ok(Json(CompactResponse {
+        deleted,
+        inserted: points.len(),
+    }))
+}
+
+async fn server_uptime(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<UptimeParams>,
+) -> Result<Json<UptimeResponse>, StatusCode> {
+    let window = range_seconds(params.range.as_deref());
+    let mut pings = state
+        .db
+        .get_pings_subset(id, None, Some(window))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Off-schedule pings would split intervals and skew the time weighting
+    if !params.include_manual {
+        pings.retain(|p| p.source != PingSource::Manual);
+    }
+    let end = now_epoch();
+    let c = coverage(
+        &pings,
+        end - window as i64,
+        end,
+        state.config.uptime_gap_secs,
+    );
+    let measured = c.online_secs + c.offline_secs;
+
+    Ok(Json(UptimeResponse {
+        uptime: (measured > 0).then(|| c.online_secs as f64 / measured as f64),
+        coverage: c,
+    }))
+}
+
+async fn list_server_incidents(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<RangeParams>,
+) -> Result<Json<Vec<Incident>>, StatusCode> {
+    let pings = state
+        .db
+        .get_pings_subset(id, None, Some(range_seconds(params.range.as_deref())))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(detect_incidents(&pings, now_epoch())))
+}
+
+async fn server_availability(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<RangeParams>,
+) -> Result<Json<AvailabilityResponse>, ApiError> {
+    if state.db.get_server_by_id(id).await?.is_none() {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let secs = range_seconds(params.range.as_deref());
+    let pings = state.db.get_pings_subset(id, None, Some(secs)).await?;
+    Ok(Json(availability(&pings, now_epoch())))
+}
+
+// A server that keeps toggling is unhealthy even when its uptime looks fine
+async fn server_flapping(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<RangeParams>,
+) -> Result<Json<FlappingResponse>, ApiError> {
+    if state.db.get_server_by_id(id).await?.is_none() {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let secs = range_seconds(params.range.as_deref());
+    let pings = state.db.get_pings_subset(id, None, Some(secs)).await?;
+    let transitions = split_segments(&pings).len().saturating_sub(1);
+    // The per-day threshold scales with the range so week/month aren't always flapping
+    let threshold = state.config.flap_threshold_per_day as u64 * secs.div_ceil(24 * 60 * 60);
+
+    Ok(Json(FlappingResponse {
+        transitions,
+        threshold,
+        flapping: transitions as u64 > threshold,
+    }))
+}
+
+// GET /api/servers/{id}/compare?range=week -> this week vs the week before, for trend arrows
+async fn compare_ranges(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<RangeParams>,
+) -> Result<Json<CompareResponse>, ApiError> {
+    if state.db.get_server_by_id(id).await?.is_none() {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let window = range_seconds(params.range.as_deref());
+    // Both windows in one query, then split at the boundary
+    let mut pings = state
+        .db
+        .get_pings_subset(id, None, Some(window * 2))
+        .await?;
+    pings.retain(|p| p.source != PingSource::Manual);
+
+    let end = now_epoch();
+    let boundary = end - window as i64;
+    let split = pings.partition_point(|p| p.pinged_at_epoch < boundary);
+    let gap = state.config.uptime_gap_secs;
+    let previous = period_stats(&pings[..split], boundary - window as i64, boundary, gap);
+    let current = period_stats(&pings[split..], boundary, end, gap);
+
+    let diff_f = |a: Option<f64>, b: Option<f64>| Some(a? - b?);
+    let delta = PeriodDelta {
+        uptime: diff_f(current.uptime, previous.uptime),
+        avg_players: diff_f(current.avg_players, previous.avg_players),
+        avg_players_pct: match (current.avg_players, previous.avg_players) {
+            (Some(cur), Some(prev)) if prev > 0.0 => Some((cur - prev) / prev * 100.0),
+            _ => None,
+        },
+        peak_players: current
+            .peak_players
+            .zip(previous.peak_players)
+            .map(|(a, b)| a - b),
+    };
+
+    Ok(Json(CompareResponse {
+        current,
+        previous,
+        delta,
+    }))
+}
+
+fn period_stats(pings: &[PingResult], start: i64, end: i64, max_gap: i64) -> PeriodStats {
+    let c = coverage(pings, start, end, max_gap);
+    let measured = c.online_secs + c.offline_secs;
+    let players: Vec<i64> = pings
+        .iter()
+        .filter(|p| p.online)
+        .filter_map(|p| p.players_online)
+        .collect();
+
+    PeriodStats {
+        start: epoch_to_rfc3339(start),
+        end: epoch_to_rfc3339(end),
+        uptime: (measured > 0).then(|| c.online_secs as f64 / measured as f64),
+        avg_players: (!players.is_empty())
+            .then(|| players.iter().sum::<i64>() as f64 / players.len() as f64),
+        peak_players: players.iter().copied().max(),
+    }
+}
+
+// GET /api/servers/{id}/diagnostics -> "is it DNS?" for a server that shows as offline
+async fn server_diagnostics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<Json<DiagnosticsResponse>, ApiError> {
+    let _ = get_admin_from_headers(&state, &headers).await?;
+    let s = state
+        .db
+        .get_server_by_id(id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let endpoint = (s.address.as_str(), s.port as u16);
+    let mut errors = Vec::new();
+
+    let mut addrs: Vec<SocketAddr> = Vec::new();
+    // Lookups get the same DNS_TIMEOUT as resolve_endpoint, the connect the ping's timeout
+    match tokio::time::timeout(DNS_TIMEOUT, tokio::net::lookup_host(endpoint)).await {
+        Ok(Ok(found)) => addrs.extend(found),
+        Ok(Err(e)) => errors.push(format!("DNS lookup failed: {}", e)),
+        Err(_) => errors.push("DNS lookup timed out".to_string()),
+    }
+    let mut resolved_ips = Vec::new();
+    for addr in &addrs {
+        let ip = addr.ip().to_string();
+        if !resolved_ips.contains(&ip) {
+            resolved_ips.push(ip);
+        }
+    }
+    let dns_ok = !addrs.is_empty();
+
+    let is_tcp = s.check_type == CheckType::Tcp;
+    // Only Java servers publish an SRV record and listen on TCP
+    let is_java = !is_tcp && s.edition == Edition::Java;
+    let srv_target = if is_java && s.address.parse::<std::net::IpAddr>().is_err() {
+        let name = format!("_minecraft._tcp.{}", s.address);
+        match tokio::time::timeout(DNS_TIMEOUT, dns::lookup_srv(&name)).await {
+            Ok(Ok(records)) => records.first().map(|r| format!("{}:{}", r.target, r.port)),
+            Ok(Err(e)) => {
+                errors.push(format!("SRV lookup failed: {}", e));
+                None
+            }
+            Err(_) => {
+                errors.push("SRV lookup timed out".to_string());
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Without an address there is nothing to connect to, and the errors would only repeat
+    let mut tcp_ok = (is_java || is_tcp).then_some(false);
+    let mut ping_ok = false;
+    if dns_ok {
+        if is_java || is_tcp {
+            tcp_ok = Some(
+                match tokio::time::timeout(
+                    state.config.ping_connect_timeout,
+                    TcpStream::connect(&addrs[..]),
+                )
+                .await
+                {
+                    Ok(Ok(_)) => true,
+                    Ok(Err(e)) => {
+                        errors.push(format!("TCP connect failed: {}", e));
+                        false
+                    }
+                    Err(_) => {
+                        errors.push("TCP connect timed out".to_string());
+                        false
+                    }
+                },
+            );
+        }
+
+        let mut row = NewPing {
+            server_id: s.id,
+            source: PingSource::Manual,
+            ..Default::default()
+        };
+        // For a TCP check the connect above already was the whole check
+        if is_tcp {
+            row.online = tcp_ok == Some(true);
+        } else {
+            ping_endpoint(&state, &s, &addrs, &mut row).await;
+        }
+        ping_ok = row.online;
+        if !ping_ok && !is_tcp {
+            let reason = match row.error_kind {
+                Some(PingErrorKind::ConnectTimeout) => " (connect timed out)",
+                Some(PingErrorKind::ReadTimeout) => " (read timed out)",
+                _ => "",
+            };
+            errors.push(format!("no answer to the status ping{}", reason));
+        }
+    }
+
+    Ok(Json(DiagnosticsResponse {
+        dns_ok,
+        resolved_ips,
+        srv_target,
+        tcp_ok,
+        ping_ok,
+        error: (!errors.is_empty()).then(|| errors.join("; ")),
+    }))
+}
+
+// GET /api/servers/{id}/latest -> the newest ping only, for status widgets
+async fn latest_ping(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<PingResult>, ApiError> {
+    let ping = state
+        .db
+        .get_last_ping_for_server(id)
+        .await?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "no pings recorded"))?;
+    Ok(Json(ping))
+}
+
+// GET /api/servers/{id}/at?time=... -> the real ping behind a (possibly averaged) graph point
+async fn ping_at(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<AtParams>,
+) -> Result<Json<PingResult>, ApiError> {
+    let time = chrono::DateTime::parse_from_rfc3339(&params.time)
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "time must be RFC 3339"))?;
+    let ping = state
+        .db
+        .get_ping_at(id, time.timestamp())
+        .await?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "no ping at or before that time"))?;
+    Ok(Json(ping))
+}
+
+// GET /api/servers/{id}/info -> server, latest ping and recent outages in one call
+async fn server_info(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<ServerInfoResponse>, ApiError> {
+    let s = state
+        .db
+        .get_server_by_id(id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let last_ping = state.db.get_last_ping_for_server(id).await?;
+    // Bounded by the retention window, nothing older is stored anyway
+    let window = PING_RETENTION_DAYS as u64 * 24 * 60 * 60;
+    let pings = state.db.get_pings_subset(id, None, Some(window)).await?;
+    let now = now_epoch();
+    let mut recent_incidents = detect_incidents(&pings, now);
+    recent_incidents.truncate(RECENT_INCIDENTS);
+    let mute_remaining_secs = s.mute_remaining(now);
+
+    Ok(Json(ServerInfoResponse {
+        muted_until: mute_remaining_secs.and(s.muted_until).map(epoch_to_rfc3339),
+        mute_remaining_secs,
+        server: ServerApi::from_server(s, last_ping.as_ref()),
+        last_ping,
+        recent_incidents,
+    }))
+}
+
+async fn server_percentiles(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<RangeParams>,
+) -> Result<Json<PercentilesResponse>, StatusCode> {
+    let counts = state
+        .db
+        .get_online_player_counts(id, range_seconds(params.range.as_deref()))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(PercentilesResponse {
+        samples: counts.len(),
+        p50: percentile(&counts, 50.0),
+        p90: percentile(&counts, 90.0),
+        p99: percentile(&counts, 99.0),
+    }))
+}
+
+async fn server_timeline(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<TimelineParams>,
+) -> Result<Json<Vec<TimelineBucket>>, StatusCode> {
+    let window = range_seconds(params.range.as_deref()) as i64;
+    let buckets = params.buckets.unwrap_or(90).clamp(1, 500);
+    // Round up so the buckets always cover the whole window
+    let bucket_secs = (window + buckets - 1) / buckets;
+    // +1 so pings from the current second still fall inside the last bucket
+    let start = now_epoch() + 1 - bucket_secs * buckets;
+
+    let counts = state
+        .db
+        .get_bucket_counts(id, start, bucket_secs)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut counts = counts.into_iter().peekable();
+    let mut res = Vec::with_capacity(buckets as usize);
+    for b in 0..buckets {
+        let bucket_start = start + b * bucket_secs;
+        let (samples, online_ratio) = match counts.next_if(|c| c.bucket == b) {
+            Some(c) => (c.samples, Some(c.online as f64 / c.samples as f64)),
+            None => (0, None),
+        };
+        res.push(TimelineBucket {
+            start: epoch_to_rfc3339(bucket_start),
+            end: epoch_to_rfc3339(bucket_start + bucket_secs),
+            samples,
+            online_ratio,
+            no_data: samples == 0,
+        });
+    }
+
+    Ok(Json(res))
+}
+
+/// Average players per bucket over `range` as a PNG line chart, for embeds where the
+/// dashboard's JS charts can't run. Uses the same buckets as the timeline.
+async fn server_chart_png(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<RangeParams>,
+) -> Result<Response, ApiError> {
+    let window = range_seconds(params.range.as_deref());
+    let key = (id, window);
+    let ttl = Duration::from_secs(CHART_CACHE_SECS);
+
+    let cached = state
+        .chart_cache
+        .lock()
+        .unwrap()
+        .get(&key)
+        .filter(|c| c.built_at.elapsed() < ttl)
+        .map(|c| c.png.clone());
+    let png = match cached {
+        Some(png) => png,
+        None => {
+            let server = state
+                .db
+                .get_server_by_id(id)
+                .await?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            let bucket_secs = (window as i64 + CHART_BUCKETS - 1) / CHART_BUCKETS;
+            let start = now_epoch() + 1 - bucket_secs * CHART_BUCKETS;
+            let counts = state.db.get_bucket_counts(id, start, bucket_secs).await?;
+
+            let mut values = vec![None; CHART_BUCKETS as usize];
+            for c in counts {
+                if let Some(slot) = values.get_mut(c.bucket as usize) {
+                    *slot = c.avg_players;
+                }
+            }
+            let line = server
+                .color
+                .as_deref()
+                .and_then(chart::parse_color)
+                .unwrap_or(chart::DEFAULT_LINE);
+            let png = tokio::task::spawn_blocking(move || chart::render_players_png(&values, line))
+                .await
+                .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+                .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+            let mut cache = state.chart_cache.lock().unwrap();
+            cache.retain(|_, c| c.built_at.elapsed() < ttl);
+            cache.insert(
+                key,
+                CachedChart {
+                    built_at: Instant::now(),
+                    png: png.clone(),
+                },
+            );
+            png
+        }
+    };
+
+    let headers = [
+        (header::CONTENT_TYPE, "image/png"),
+        (header::CACHE_CONTROL, "max-age=60"),
+    ];
+    Ok((headers, png).into_response())
+}
+
+// GET /api/metrics -> Prometheus text format, private servers only for admins
+async fn prometheus_metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let admin = is_admin(&state, &headers).await;
+    let servers = state.db.list_servers().await?;
+    let ids: Vec<i64> = servers.iter().map(|s| s.id).collect();
+    state.latency_histograms.retain(&ids);
+    let servers: Vec<(i64, String)> = servers
+        .into_iter()
+        .filter(|s| admin || s.is_public)
+        .map(|s| (s.id, s.name))
+        .collect();
+    let body = state.latency_histograms.render(&servers);
+    Ok(([(header::CONTENT_TYPE, metrics::CONTENT_TYPE)], body).into_response())
+}
+
+// GET /api/versions -> which versions the fleet runs right now, most common first
+async fn list_versions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<VersionCount>>, ApiError> {
+    let admin = is_admin(&state, &headers).await;
+    let versions = state.db.count_current_versions(admin).await?;
+    Ok(Json(
+        versions
+            .into_iter()
+            .map(|(version, servers)| VersionCount { version, servers })
+            .collect(),
+    ))
+}
+
+async fn group_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(tag): Path<String>,
+    Query(params): Query<RangeParams>,
+) -> Result<Json<GroupStatusResponse>, StatusCode> {
+    let tag = normalize_tag(&tag);
+    let admin = is_admin(&state, &headers).await;
+    let servers = state
+        .db
+        .list_servers_by_tag(&tag, admin)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if servers.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut online = 0;
+    let mut players = 0;
+    for s in &servers {
+        let last = state
+            .db
+            .get_last_ping_for_server(s.id)
+            .await
+            .unwrap_or(None);
+        if let Some(p) = last.filter(|p| p.online) {
+            online += 1;
+            players += p.players_online.unwrap_or(0);
+        }
+    }
+
+    let (samples, online_samples) = state
+        .db
+        .get_group_ping_counts(&tag, range_seconds(params.range.as_deref()), admin)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(GroupStatusResponse {
+        tag,
+        servers: servers.len(),
+        online,
+        players,
+        uptime: (samples > 0).then(|| online_samples as f64 / samples as f64),
+    }))
+}
+
+async fn server_heatmap(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<HeatmapParams>,
+) -> Result<Json<HeatmapResponse>, StatusCode> {
+    let offset_mins = params.tz_offset.unwrap_or(0).clamp(-14 * 60, 14 * 60);
+    let slots = state
+        .db
+        .get_hourly_activity(id, range_seconds(params.range.as_deref()), offset_mins * 60)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Hour-of-day averages are weighted by samples so busy weekdays count for more
+    let mut hour_totals = [(0.0, 0i64); 24];
+    let mut days = vec![vec![None; 24]; 7];
+    for slot in &slots {
+        let (day, hour) = (slot.weekday as usize % 7, slot.hour as usize % 24);
+        days[day][hour] = Some(slot.avg_players);
+        hour_totals[hour].0 += slot.avg_players * slot.samples as f64;
+        hour_totals[hour].1 += slot.samples;
+    }
+    let hours = hour_totals
+        .iter()
+        .map(|&(sum, n)| (n > 0).then(|| sum / n as f64))
+        .collect();
+
+    Ok(Json(HeatmapResponse {
+        hours,
+        days: params.by_weekday.then_some(days),
+    }))
+}
+
+async fn server_motd_history(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<RangeParams>,
+) -> Result<Json<Vec<MotdPeriod>>, ApiError> {
+    if state.db.get_server_by_id(id).await?.is_none() {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let motds = state
+        .db
+        .get_motds(id, range_seconds(params.range.as_deref()))
+        .await?;
+    Ok(Json(motd_periods(motds)))
+}
+
+// Collapses consecutive identical MOTDs. A MOTD that comes back later starts a new period.
+fn motd_periods(motds: Vec<(String, String)>) -> Vec<MotdPeriod> {
+    let mut periods: Vec<MotdPeriod> = Vec::new();
+    for (pinged_at, motd) in motds {
+        match periods.last_mut() {
+            Some(p) if p.motd == motd => {
+                p.last_seen = pinged_at;
+                p.samples += 1;
+            }
+            _ => periods.push(MotdPeriod {
+                motd,
+                first_seen: pinged_at.clone(),
+                last_seen: pinged_at,
+                samples: 1,
+            }),
+        }
+    }
+    periods
+}
+
+async fn server_badge(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<BadgeParams>,
+) -> Result<Response, StatusCode> {
+    let server = state
+        .db
+        .get_server_by_id(id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let last = state
+        .db
+        .get_last_ping_for_server(id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let (message, color) = match last {
+        Some(p) if p.online => {
+            let message = match p.players_online {
+                Some(n) => format!("online | {} players", n),
+                None => "online".to_string(),
+            };
+            (message, badge::COLOR_ONLINE)
+        }
+        Some(_) => ("offline".to_string(), badge::COLOR_OFFLINE),
+        None => ("unknown".to_string(), badge::COLOR_UNKNOWN),
+    };
+
+    let mut headers = HeaderMap::new();
+    // Badges get embedded in READMEs, keep proxies/CDNs from holding them for long
+    headers.insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("max-age=60"),
+    );
+
+    if params.format.as_deref() == Some("shields") {
+        let body = ShieldsEndpoint {
+            schema_version: 1,
+            label: server.name,
+            message,
+            color: color.trim_start_matches('#').to_string(),
+        };
+        return Ok((headers, Json(body)).into_response());
+    }
+
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("image/svg+xml"),
+    );
+    Ok((headers, badge::render_svg(&server.name, &message, color)).into_response())
+}
+
+#[derive(Serialize)]
+struct NetworkPlayersResponse {
+    bucket_secs: i64,
+    points: Vec<NetworkPlayersPoint>,
+}
+
+#[derive(Serialize)]
+struct NetworkPlayersPoint {
+    // Start of the bucket
+    time: String,
+    // None when no server was pinged in the bucket
+    players: Option<i64>,
+    servers: i64,
+}
+
+// GET /api/network/players?range=week -> total players across all servers over time.
+// Pings of different servers don't line up, so each server adds its latest value per bucket
+async fn network_players(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<RangeParams>,
+) -> Result<Json<NetworkPlayersResponse>, ApiError> {
+    let admin = is_admin(&state, &headers).await;
+    let window = range_seconds(params.range.as_deref()) as i64;
+    // One ping interval for a day, coarser for longer ranges to keep ~150 points
+    let bucket_secs = match params.range.as_deref() {
+        Some("week") => 60 * 60,
+        Some("month") => 4 * 60 * 60,
+        _ => PING_INTERVAL_SECS as i64,
+    };
+    let buckets = window / bucket_secs;
+    // Aligned like the ping cycles, so a bucket holds whole cycles
+    let start = (now_epoch() / bucket_secs + 1 - buckets) * bucket_secs;
+    let rows = state
+        .db
+        .get_network_player_buckets(start, bucket_secs, admin)
+        .await?;
+
+    let mut points: Vec<NetworkPlayersPoint> = (0..buckets)
+        .map(|i| NetworkPlayersPoint {
+            time: epoch_to_rfc3339(start + i * bucket_secs),
+            players: None,
+            servers: 0,
+        })
+        .collect();
+    for row in rows {
+        if let Some(p) = points.get_mut(row.bucket as usize) {
+            p.players = Some(row.players);
+            p.servers = row.servers;
+        }
+    }
+    Ok(Json(NetworkPlayersResponse {
+        bucket_secs,
+        points,
+    }))
+}
+
+// Maps the `range` query param to a window length in seconds
+fn range_seconds(range: Option<&str>) -> u64 {
+    match range {
+        Some("week") => 60 * 60 * 24 * 7,
+        Some("month") => 60 * 60 * 24 * 30,
+        _ => 60 * 60 * 24, // "day" and default
     }
+}
 
-    if raw_pings.is_empty() {
-        return Ok(Json(Vec::new()));
+// Nearest-rank percentile over an already sorted slice
+fn percentile(sorted: &[i64], p: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
     }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
 
-    // 2. Downsampling aggressiveness
-    // per_chunk_secs = how coarse we compress long online segments
-    let per_chunk_secs: i64 = match params.range.as_deref() {
-        Some("month") => 6 * 60 * 60, // 6h chunks -> ~4 points per day -> 116-128
-        Some("week") => 60 * 60,      // 1h chunks -> ~24 points per day -> 168 per week
-        _ => 15 * 60,
-    };
+// ==========================================
+// SEGMENT COMPRESSION LOGIC
+// ==========================================
 
-    // Treat any segment shorter than this as a "blip"
-    let short_blip_secs: i64 = 20 * 60; // 20 minutes
+/// Time within a window split by what the pings say about it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+struct Coverage {
+    online_secs: i64,
+    offline_secs: i64,
+    // Not covered by any ping, e.g. while the pinger itself was down
+    no_data_secs: i64,
+}
 
-    let mut optimized = Vec::new();
+// Each ping vouches for its state until the next one. If the next one is more than
+// `max_gap` away the pinger wasn't running, so only one interval is credited and the
+// rest is "no data" instead of being blamed on the server.
+fn coverage(raw: &[PingResult], window_start: i64, window_end: i64, max_gap: i64) -> Coverage {
+    let mut c = Coverage::default();
+    let interval = PING_INTERVAL_SECS as i64;
+    for (i, p) in raw.iter().enumerate() {
+        let start = p.pinged_at_epoch.max(window_start);
+        let next = raw
+            .get(i + 1)
+            .map(|n| n.pinged_at_epoch)
+            .unwrap_or(window_end);
+        let end = if next - p.pinged_at_epoch > max_gap {
+            p.pinged_at_epoch + interval
+        } else {
+            next
+        }
+        .min(window_end);
+        let secs = (end - start).max(0);
+        if p.online {
+            c.online_secs += secs;
+        } else {
+            c.offline_secs += secs;
+        }
+    }
+    c.no_data_secs = (window_end - window_start - c.online_secs - c.offline_secs).max(0);
+    c
+}
 
-    // TODO:
-    // Fix this to actually do what i want
+// Inclusive index range of consecutive pings sharing the same online state
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    start: usize,
+    end: usize,
+    online: bool,
+}
 
-    // 3. Split into segments where online/offline remains constant
-    let mut seg_start = 0usize;
-    let mut seg_state = raw_pings[0].online;
-
-    for idx in 1..raw_pings.len() {
-        let state_changed = raw_pings[idx].online != seg_state;
-        if state_changed {
-            compress_segment(
-                &raw_pings,
-                seg_start,
-                idx - 1,
-                seg_state,
-                per_chunk_secs,
-                short_blip_secs,
-                &mut optimized,
-            );
-            seg_start = idx;
-            seg_state = raw_pings[idx].online;
+fn split_segments(raw: &[PingResult]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    for (idx, p) in raw.iter().enumerate() {
+        match segments.last_mut() {
+            Some(seg) if seg.online == p.online => seg.end = idx,
+            _ => segments.push(Segment {
+                start: idx,
+                end: idx,
+                online: p.online,
+            }),
         }
     }
+    segments
+}
 
-    // last segment
-    compress_segment(
-        &raw_pings,
-        seg_start,
-        raw_pings.len() - 1,
-        seg_state,
-        per_chunk_secs,
-        short_blip_secs,
-        &mut optimized,
-    );
+// First ping of every segment, plus the final ping so the window's end is kept
+fn transition_points(raw: &[PingResult]) -> Vec<PingResult> {
+    let mut out: Vec<PingResult> = split_segments(raw)
+        .iter()
+        .map(|seg| raw[seg.start].clone())
+        .collect();
+    if let Some(last) = raw.last()
+        && out.last().is_some_and(|p| p.id != last.id)
+    {
+        out.push(last.clone());
+    }
+    out
+}
 
-    Ok(Json(optimized))
+/// One outage: a run of offline pings, ended by the next online ping.
+#[derive(Debug, Clone, Serialize)]
+struct Incident {
+    start: String,
+    // None while the server is still down
+    end: Option<String>,
+    duration_secs: i64,
+}
+
+// Outages in `raw` (oldest first), newest first. Ongoing ones run until `now`.
+fn detect_incidents(raw: &[PingResult], now: i64) -> Vec<Incident> {
+    let mut incidents: Vec<Incident> = split_segments(raw)
+        .iter()
+        .filter(|seg| !seg.online)
+        .map(|seg| {
+            let (start, end) = segment_span(raw, seg);
+            Incident {
+                start: epoch_to_rfc3339(start),
+                end: end.map(epoch_to_rfc3339),
+                duration_secs: end.unwrap_or(now) - start,
+            }
+        })
+        .collect();
+    incidents.reverse();
+    incidents
+}
+
+// A segment lasts from its first ping until the first ping of the next one, `None` for
+// the last segment, which is still going
+fn segment_span(raw: &[PingResult], seg: &Segment) -> (i64, Option<i64>) {
+    (
+        raw[seg.start].pinged_at_epoch,
+        raw.get(seg.end + 1).map(|p| p.pinged_at_epoch),
+    )
+}
+
+// Same segments as `detect_incidents`, so the longest downtime is the longest incident
+fn availability(raw: &[PingResult], now: i64) -> AvailabilityResponse {
+    let mut longest_uptime_secs = 0;
+    let mut longest_downtime_secs = 0;
+    for seg in split_segments(raw) {
+        let (start, end) = segment_span(raw, &seg);
+        let secs = end.unwrap_or(now) - start;
+        let longest = if seg.online {
+            &mut longest_uptime_secs
+        } else {
+            &mut longest_downtime_secs
+        };
+        *longest = secs.max(*longest);
+    }
+    let online_at = |p: &PingResult| epoch_to_rfc3339(p.pinged_at_epoch);
+    AvailabilityResponse {
+        first_online: raw.iter().find(|p| p.online).map(online_at),
+        last_online: raw.iter().rfind(|p| p.online).map(online_at),
+        longest_uptime_secs,
+        longest_downtime_secs,
+    }
+}
+
+// Runs `compress_segment` over every constant online/offline stretch of `raw`
+fn downsample(raw: &[PingResult], per_chunk_secs: i64, blip_secs: i64) -> Vec<PingResult> {
+    let mut out = Vec::new();
+    for seg in split_segments(raw) {
+        compress_segment(
+            raw,
+            seg.start,
+            seg.end,
+            seg.online,
+            per_chunk_secs,
+            blip_secs,
+            &mut out,
+        );
+    }
+    out
+}
+
+// Evenly spaced subset of at most `max` points (at least 2), keeping the first and last
+fn decimate(points: Vec<PingResult>, max: usize) -> Vec<PingResult> {
+    if points.len() <= max {
+        return points;
+    }
+    let last = points.len() - 1;
+    (0..max)
+        .map(|i| points[i * last / (max - 1)].clone())
+        .collect()
 }
 
-// ==========================================
-// SEGMENT COMPRESSION LOGIC
-// ==========================================
 fn compress_segment(
     raw: &[PingResult],
     start: usize,
@@ -449,17 +3621,15 @@ fn compress_segment(
     let first = &raw[start];
     let last = &raw[end];
 
-    let start_time = parse_time(&first.pinged_at);
-    let end_time = parse_time(&last.pinged_at);
+    let start_time = first.pinged_at_epoch;
+    let end_time = last.pinged_at_epoch;
     let duration = end_time - start_time;
     let len = end + 1 - start;
 
     // 1) Very short segments -> blips (keep them detailed)
     if duration <= blip_secs {
         if len <= 2 {
-            for idx in start..=end {
-                out.push(raw[idx].clone());
-            }
+            out.extend_from_slice(&raw[start..=end]);
         } else {
             out.push(first.clone());
             out.push(last.clone());
@@ -476,24 +3646,22 @@ fn compress_segment(
 
     // 3) Long online segment -> downsample into coarse chunks
     let mut chunk_ref_idx = start;
-    let mut chunk_start_time = parse_time(&raw[start].pinged_at);
+    let mut chunk_start_time = raw[start].pinged_at_epoch;
     let mut chunk_sum_players: i64 = 0;
     let mut chunk_count: i64 = 0;
 
     for idx in start..=end {
         let p = &raw[idx];
-        let t = parse_time(&p.pinged_at);
+        let t = p.pinged_at_epoch;
 
-        chunk_sum_players += p.players_online.unwrap_or(0) as i64;
+        chunk_sum_players += p.players_online.unwrap_or(0);
         chunk_count += 1;
 
         if t - chunk_start_time >= per_chunk_secs {
             let mut avg_ping = raw[chunk_ref_idx].clone();
-            if chunk_count > 0 {
-                let avg = chunk_sum_players / chunk_count;
-                avg_ping.players_online = Some(avg);
-            }
+            set_players_avg(&mut avg_ping, chunk_sum_players, chunk_count);
             avg_ping.pinged_at = p.pinged_at.clone();
+            avg_ping.pinged_at_epoch = t;
             out.push(avg_ping);
 
             chunk_ref_idx = idx;
@@ -506,19 +3674,29 @@ fn compress_segment(
     // Flush final partial chunk
     if chunk_count > 0 {
         let mut avg_ping = raw[chunk_ref_idx].clone();
-        let avg = chunk_sum_players / chunk_count;
-        avg_ping.players_online = Some(avg);
+        set_players_avg(&mut avg_ping, chunk_sum_players, chunk_count);
         out.push(avg_ping);
     }
 }
 
-// Helper to parse SQL date string to seconds (simplistic for this logic)
-fn parse_time(t: &str) -> i64 {
-    use chrono::DateTime;
+// Integer division would floor: 3 players over 2 pings must show as 2 (1.5), not 1
+fn set_players_avg(p: &mut PingResult, sum: i64, count: i64) {
+    let avg = sum as f64 / count as f64;
+    p.players_online = Some(avg.round() as i64);
+    p.players_avg = Some((avg * 10.0).round() / 10.0);
+}
+
+fn now_epoch() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
 
-    DateTime::parse_from_rfc3339(t)
+fn epoch_to_rfc3339(t: i64) -> String {
+    chrono::DateTime::from_timestamp(t, 0)
         .unwrap_or_default()
-        .timestamp()
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
 }
 
 // Utilities
@@ -528,76 +3706,508 @@ async fn ping_all_servers_concurrently(state: &AppState) -> Result<(), ()> {
         .db
         .list_servers()
         .await
-        .map_err(|e| eprintln!("Ping list error: {:?}", e))?;
-    for s in servers {
-        let st = state.clone();
-        tokio::spawn(async move {
-            let _ = ping_one_server(&st, s.id).await;
-        });
-    }
+        .map_err(|e| error!("Ping list error: {:?}", e))?;
+    let jitter = state.config.ping_jitter_secs;
+    let started = Instant::now();
+    let count = servers.len();
+    let pings: Vec<_> = servers
+        .into_iter()
+        .map(|s| {
+            let st = state.clone();
+            tokio::spawn(async move {
+                if jitter > 0 {
+                    sleep(Duration::from_secs(jitter_offset(s.id, jitter))).await;
+                }
+                ping_one_server_isolated(st, s.id).await;
+            })
+        })
+        .collect();
+
+    // Timed in the background so the caller's schedule isn't pushed back by slow pings
+    let st = state.clone();
+    tokio::spawn(async move {
+        for ping in pings {
+            let _ = ping.await;
+        }
+        let took = started.elapsed();
+        if took > Duration::from_secs(PING_INTERVAL_SECS) {
+            warn!(
+                "Pinging {} servers took {:.0}s, longer than the {}s interval. \
+                 Cycles are overlapping, track fewer servers or lower PING_JITTER_SECS.",
+                count,
+                took.as_secs_f64(),
+                PING_INTERVAL_SECS
+            );
+        }
+        st.record_ping_cycle(count, took);
+    });
     Ok(())
 }
 
-async fn ping_one_server(state: &AppState, id: i64) -> Result<(), ()> {
+// Stable per-server delay in [0, jitter) so pings spread out but keep a steady cadence
+fn jitter_offset(server_id: i64, jitter: u64) -> u64 {
+    // Knuth multiplicative hash, neighbouring ids land far apart
+    (server_id as u64).wrapping_mul(2_654_435_761) % jitter
+}
+
+// Runs the ping in its own task so a panic in it is caught here instead of the server
+// silently dropping off the graph. The panic is logged and recorded as an offline ping
+// plus a `ping_panic` event.
+async fn ping_one_server_isolated(state: AppState, id: i64) {
+    let st = state.clone();
+    let Err(e) =
+        tokio::spawn(async move { ping_one_server(&st, id, PingSource::Scheduled).await }).await
+    else {
+        return;
+    };
+    if !e.is_panic() {
+        return;
+    }
+    let payload = e.into_panic();
+    let msg = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    error!("Ping of server {} panicked: {}", id, msg);
+
+    let row = NewPing {
+        server_id: id,
+        ..Default::default()
+    };
+    let _ = state.db.insert_ping_result(&row).await;
+    let _ = state.db.insert_server_event(id, "ping_panic", &msg).await;
+    state.invalidate_servers_cache();
+}
+
+/// Pings one server and stores the result. Returns the id of the row holding it, which is an
+/// older row when the ping was folded into a collapsed outage, or `None` when the server
+/// doesn't exist or the write failed.
+/// If a ping of the same server is already running (a manual one overlapping the scheduled
+/// one), this waits for it and returns its row instead of storing a near-duplicate.
+async fn ping_one_server(state: &AppState, id: i64, source: PingSource) -> Result<Option<i64>, ()> {
+    let slot = state.ping_slot(id);
+    let Ok(mut last) = slot.try_lock() else {
+        return Ok(*slot.lock().await);
+    };
+    let result = ping_and_insert(state, id, source).await;
+    *last = result.unwrap_or_default();
+    result
+}
+
+async fn ping_and_insert(state: &AppState, id: i64, source: PingSource) -> Result<Option<i64>, ()> {
     let s = match state.db.get_server_by_id(id).await {
         Ok(Some(v)) => v,
-        _ => return Ok(()),
+        _ => return Ok(None),
+    };
+
+    let mut row = NewPing {
+        server_id: s.id,
+        source,
+        ..Default::default()
+    };
+    match resolve_endpoint(&s).await {
+        Ok(addrs) => {
+            // DNS is left out, the round trip is what the latency histograms are about
+            let sent = Instant::now();
+            ping_endpoint(state, &s, &addrs, &mut row).await;
+            if row.online {
+                row.latency_ms = Some(sent.elapsed().as_millis() as i64);
+            }
+        }
+        // Not the server's fault, keep it apart from real outages in the history
+        Err(_) => row.error_kind = Some(PingErrorKind::Dns),
+    }
+    // Whatever the server sent ends up in every history response, keep it bounded
+    truncate_ellipsis(&mut row.motd, state.config.max_motd_len);
+    truncate_ellipsis(&mut row.version, state.config.max_version_len);
+    if s.enable_query && row.online {
+        row.query_data = run_query(&s).await;
+    }
+    let previous = state
+        .db
+        .get_last_ping_for_server(s.id)
+        .await
+        .unwrap_or(None);
+    if let Some(hash) = &row.favicon_hash
+        && let Ok(Some(previous)) = state.db.get_last_favicon_hash(s.id).await
+        && &previous != hash
+    {
+        // A different icon on the same address usually means a rehost or a new owner
+        row.favicon_changed = true;
+        let detail = format!("{} -> {}", short_hash(&previous), short_hash(hash));
+        let _ = state
+            .db
+            .insert_server_event(s.id, "favicon_changed", &detail)
+            .await;
+    }
+    let collapsed_into = if state.config.collapse_repeated_offline
+        && !row.online
+        && row.source == PingSource::Scheduled
+    {
+        state
+            .db
+            .collapse_repeated_offline(s.id, row.error_kind)
+            .await
+            .unwrap_or(None)
+    } else {
+        None
+    };
+    let inserted = match collapsed_into {
+        Some(id) => Some(id),
+        None => state.db.insert_ping_result(&row).await.ok(),
+    };
+    state.invalidate_servers_cache();
+    if let Some(ms) = row.latency_ms {
+        state.latency_histograms.observe(s.id, ms as f64);
+    }
+    confirm_transition(state, &s, &row).await;
+    if let Some(threshold) = s.player_alert_threshold
+        && crossed_player_threshold(previous.as_ref(), &row, threshold)
+    {
+        notify_high_player_count(state, &s, &row, threshold).await;
+    }
+    Ok(inserted)
+}
+
+// Don't repeat a capacity alert within this window, even if the count dipped and came back
+const HIGH_PLAYERS_REALERT_SECS: i64 = 3600;
+
+// Only the upward crossing counts, staying above the threshold is a single alert
+fn crossed_player_threshold(previous: Option<&PingResult>, row: &NewPing, threshold: i64) -> bool {
+    let above = |players: Option<i64>| players.is_some_and(|n| n >= threshold);
+    row.online
+        && above(row.players_online)
+        && !previous.is_some_and(|p| p.online && above(p.players_online))
+}
+
+// Capacity alert, recorded as a `high_player_count` event which also debounces it
+async fn notify_high_player_count(state: &AppState, s: &Server, row: &NewPing, threshold: i64) {
+    let recent = state
+        .db
+        .has_recent_server_event(s.id, "high_player_count", HIGH_PLAYERS_REALERT_SECS)
+        .await
+        .unwrap_or(true);
+    if recent {
+        return;
+    }
+    let players = row.players_online.unwrap_or_default();
+    let detail = format!("{players} players (threshold {threshold})");
+    let _ = state
+        .db
+        .insert_server_event(s.id, "high_player_count", &detail)
+        .await;
+    if s.mute_remaining(now_epoch()).is_some() {
+        return;
+    }
+    let message = match row.players_max {
+        Some(max) => format!(
+            "{} ({}) has {players}/{max} players online",
+            s.name, s.address
+        ),
+        None => format!("{} ({}) has {players} players online", s.name, s.address),
+    };
+    state.notifier.send(s.webhook_url.as_deref(), message);
+}
+
+// Cuts `text` to at most `max` characters, the last one being '…'
+fn truncate_ellipsis(text: &mut Option<String>, max: usize) {
+    if let Some(t) = text
+        && let Some((end, _)) = t.char_indices().nth(max)
+    {
+        let keep = t[..end].char_indices().last().map_or(0, |(i, _)| i);
+        t.truncate(keep);
+        t.push('…');
+    }
+}
+
+// Records the new state once enough pings in a row agree, and alerts on a change
+async fn confirm_transition(state: &AppState, s: &Server, row: &NewPing) {
+    if s.confirmed_online == Some(row.online) {
+        return;
+    }
+    let required = if row.online {
+        state.config.up_confirm_pings
+    } else {
+        state.config.down_confirm_pings
+    };
+    let streak = state
+        .db
+        .count_trailing_pings(s.id, row.online, required)
+        .await
+        .unwrap_or(0);
+    if streak < required
+        || state
+            .db
+            .set_confirmed_online(s.id, row.online)
+            .await
+            .is_err()
+    {
+        return;
+    }
+    if s.confirmed_online.is_some() {
+        notify_transition(state, s, row);
+    }
+}
+
+// Up/down alert, unless the server is muted
+fn notify_transition(state: &AppState, s: &Server, row: &NewPing) {
+    if s.mute_remaining(now_epoch()).is_some() {
+        return;
+    }
+    let message = if row.online {
+        format!("{} ({}) is back online", s.name, s.address)
+    } else {
+        format!("{} ({}) went offline", s.name, s.address)
+    };
+    state.notifier.send(s.webhook_url.as_deref(), message);
+}
+
+// Resolver hiccups are common enough that one failed lookup shouldn't mark a server down
+const DNS_RETRY_DELAY: Duration = Duration::from_secs(1);
+const DNS_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Looks the address up once, retrying a single time on failure
+async fn resolve_endpoint(s: &Server) -> std::io::Result<Vec<SocketAddr>> {
+    let lookup = || async {
+        let addrs = tokio::time::timeout(
+            DNS_TIMEOUT,
+            tokio::net::lookup_host((s.address.as_str(), s.port as u16)),
+        )
+        .await
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??
+        .collect::<Vec<_>>();
+        if addrs.is_empty() {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotFound));
+        }
+        Ok(addrs)
     };
+    match lookup().await {
+        Ok(addrs) => Ok(addrs),
+        Err(_) => {
+            sleep(DNS_RETRY_DELAY).await;
+            lookup().await
+        }
+    }
+}
+
+// Runs the server's kind of check against the already resolved `addrs`
+async fn ping_endpoint(state: &AppState, s: &Server, addrs: &[SocketAddr], row: &mut NewPing) {
+    match (s.check_type, s.edition) {
+        (CheckType::Tcp, _) => ping_tcp(state, addrs, row).await,
+        (CheckType::Minecraft, Edition::Bedrock) => ping_bedrock(state, addrs, row).await,
+        (CheckType::Minecraft, Edition::Java) => ping_java(state, s, addrs, row).await,
+    }
+}
 
-    // WRAP THE NETWORK LOGIC IN A TIMEOUT
-    // This ensures we never hang longer than 3 seconds per server
-    let ping_logic = async {
-        let mut stream = TcpStream::connect((s.address.as_str(), s.port as u16)).await?;
-        ping(&mut stream, s.address.as_str(), s.port as u16).await
+// Online when a TCP connection opens, nothing is sent
+async fn ping_tcp(state: &AppState, addrs: &[SocketAddr], row: &mut NewPing) {
+    let connect =
+        tokio::time::timeout(state.config.ping_connect_timeout, TcpStream::connect(addrs));
+    match connect.await {
+        Ok(Ok(_)) => row.online = true,
+        Ok(Err(_)) => row.error_kind = Some(PingErrorKind::Connect),
+        Err(_) => row.error_kind = Some(PingErrorKind::ConnectTimeout),
+    }
+}
+
+// Fills `row` from a Java status ping to the already resolved `addrs`
+async fn ping_java(state: &AppState, s: &Server, addrs: &[SocketAddr], row: &mut NewPing) {
+    // Connect and read are timed separately, so a server that accepts the connection
+    // but never answers shows up as a read timeout instead of looking unreachable
+    let connect =
+        tokio::time::timeout(state.config.ping_connect_timeout, TcpStream::connect(addrs));
+    let stream = match connect.await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(_)) => {
+            row.error_kind = Some(PingErrorKind::Connect);
+            return;
+        }
+        Err(_) => {
+            row.error_kind = Some(PingErrorKind::ConnectTimeout);
+            return;
+        }
+    };
+    let protocol = s.protocol_hint.map(|p| p as i32);
+    let mut stream = ProtocolOverride::new(stream, protocol);
+    let host = if s.modded {
+        handshake::forge_host(&s.address, s.protocol_hint)
+    } else {
+        s.address.clone()
     };
+    let ping_logic = ping(&mut stream, &host, s.port as u16);
 
-    match tokio::time::timeout(Duration::from_secs(3), ping_logic).await {
+    match tokio::time::timeout(state.config.ping_read_timeout, ping_logic).await {
         Ok(Ok(r)) => {
             // Success!
             let desc = r
                 .description
                 .as_ref()
-                .map(|v| v.to_string())
+                .map(motd::from_description)
                 .unwrap_or_default();
-            let _ = state
-                .db
-                .insert_ping_result(
-                    s.id,
-                    true,
-                    None,
-                    Some(r.online_players as i64),
-                    Some(r.max_players as i64),
-                    Some(r.version.as_str()),
-                    Some(desc.as_str()),
-                )
-                .await;
+            let max = state.config.max_player_count;
+            let (online, raw) = clamp_player_count(r.online_players, max);
+            row.online = true;
+            row.players_online = Some(online);
+            row.players_online_raw = raw;
+            row.players_max = Some(clamp_player_count(r.max_players, max).0);
+            row.mods = forge_mods(&r).map(|mods| serde_json::to_string(&mods).unwrap());
+            let sample = r.sample.as_deref().unwrap_or_default();
+            row.player_sample = Some(serde_json::to_string(sample).unwrap());
+            row.version = Some(r.version);
+            row.motd = Some(desc);
+            row.favicon_hash = Some(r.favicon.as_deref().map(favicon_hash).unwrap_or_default());
         }
-        _ => {
-            // Either Timeout (Err) or Ping Error (Ok(Err))
-            // We treat both as offline
-            let _ = state
-                .db
-                .insert_ping_result(s.id, false, None, None, None, None, None)
-                .await;
+        Ok(Err(craftping::Error::UnsupportedProtocol)) => {
+            // The server answered, we just couldn't read the status JSON.
+            // It's reachable, so don't count it as down.
+            row.online = true;
+            row.parse_error = true;
         }
+        Ok(Err(_)) => row.error_kind = Some(PingErrorKind::Connect),
+        Err(_) => row.error_kind = Some(PingErrorKind::ReadTimeout),
+    }
+}
+
+#[derive(Serialize)]
+struct ForgeModApi {
+    id: String,
+    version: String,
+}
+
+// Mods from `forgeData` (FML2) or `modinfo` (FML1), None for vanilla servers
+fn forge_mods(r: &craftping::Response) -> Option<Vec<ForgeModApi>> {
+    if let Some(data) = &r.forge_data {
+        return Some(
+            data.mods
+                .iter()
+                .map(|m| ForgeModApi {
+                    id: m.mod_id.clone(),
+                    version: m.mod_marker.clone(),
+                })
+                .collect(),
+        );
+    }
+    let info = r.mod_info.as_ref()?;
+    Some(
+        info.mod_list
+            .iter()
+            .map(|m| ForgeModApi {
+                id: m.mod_id.clone(),
+                version: m.version.clone(),
+            })
+            .collect(),
+    )
+}
+
+// Fills `row` from a Bedrock unconnected ping, same rules as the Java one.
+// UDP can't tell a dead address from a slow one, so only the first address is tried.
+async fn ping_bedrock(state: &AppState, addrs: &[SocketAddr], row: &mut NewPing) {
+    // Connectionless, so the whole exchange counts as reading
+    let ping_logic = bedrock::ping(addrs[0]);
+    match tokio::time::timeout(state.config.ping_read_timeout, ping_logic).await {
+        Ok(Ok(r)) => {
+            let max = state.config.max_player_count;
+            let (online, raw) = clamp_player_count(r.online_players, max);
+            row.online = true;
+            row.players_online = Some(online);
+            row.players_online_raw = raw;
+            row.players_max = Some(clamp_player_count(r.max_players, max).0);
+            row.version = Some(r.version);
+            row.motd = Some(motd::strip_formatting(&r.motd));
+        }
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::InvalidData => {
+            row.online = true;
+            row.parse_error = true;
+        }
+        Ok(Err(_)) => row.error_kind = Some(PingErrorKind::Connect),
+        Err(_) => row.error_kind = Some(PingErrorKind::ReadTimeout),
+    }
+}
+
+fn favicon_hash(png: &[u8]) -> String {
+    hex::encode(Sha256::digest(png))
+}
+
+// Enough of the hash for event details, "none" when there was no favicon
+fn short_hash(hash: &str) -> &str {
+    if hash.is_empty() {
+        "none"
+    } else {
+        &hash[..hash.len().min(12)]
+    }
+}
+
+// Full player list over UDP Query. Plenty of servers leave query disabled,
+// so any failure just means no data for this ping.
+async fn run_query(s: &Server) -> Option<String> {
+    let port = s.query_port.unwrap_or(s.port) as u16;
+    let data = tokio::time::timeout(
+        Duration::from_secs(3),
+        query::full_stat(s.address.as_str(), port),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    serde_json::to_string(&data).ok()
+}
+
+// Caps absurd counts (fake numbers from proxies) so they don't wreck graph scales.
+// Returns the stored value and, when clamped, the original.
+fn clamp_player_count(reported: usize, max: i64) -> (i64, Option<i64>) {
+    let reported = i64::try_from(reported).unwrap_or(i64::MAX);
+    if reported > max {
+        (max, Some(reported))
+    } else {
+        (reported, None)
     }
-    Ok(())
 }
 
 // Auth Utilities
-fn hash_password(p: &str) -> String {
+fn hash_password(config: &Config, p: &str) -> String {
     let mut salt = [0u8; 16];
     OsRng.fill_bytes(&mut salt);
-    Argon2::default()
+    config
+        .argon2()
         .hash_password(p.as_bytes(), &SaltString::encode_b64(&salt).unwrap())
         .unwrap()
         .to_string()
 }
-fn verify_password(h: &str, p: &str) -> bool {
+// The cost comes from the stored hash, so older hashes keep verifying after a config change
+fn verify_password(config: &Config, h: &str, p: &str) -> bool {
     PasswordHash::new(h)
-        .map(|ph| Argon2::default().verify_password(p.as_bytes(), &ph).is_ok())
+        .map(|ph| config.argon2().verify_password(p.as_bytes(), &ph).is_ok())
+        .unwrap_or(false)
+}
+// argon2 is slow on purpose (ARGON2_MEMORY_KIB goes up to 4 GiB), so request handlers run
+// it on the blocking pool instead of stalling a runtime thread
+async fn hash_password_blocking(config: &Arc<Config>, p: &str) -> String {
+    let (config, p) = (config.clone(), p.to_string());
+    tokio::task::spawn_blocking(move || hash_password(&config, &p))
+        .await
+        .expect("password hashing panicked")
+}
+async fn verify_password_blocking(config: &Arc<Config>, h: &str, p: &str) -> bool {
+    let (config, h, p) = (config.clone(), h.to_string(), p.to_string());
+    tokio::task::spawn_blocking(move || verify_password(&config, &h, &p))
+        .await
         .unwrap_or(false)
 }
+// True when the stored hash isn't Argon2id with exactly the configured cost
+fn needs_rehash(config: &Config, h: &str) -> bool {
+    let Ok(ph) = PasswordHash::new(h) else {
+        return false;
+    };
+    let Ok(params) = Params::try_from(&ph) else {
+        return true;
+    };
+    ph.algorithm != Algorithm::Argon2id.ident()
+        || ph.version != Some(Version::V0x13.into())
+        || params.m_cost() != config.argon2_memory_kib
+        || params.t_cost() != config.argon2_iterations
+        || params.p_cost() != config.argon2_parallelism
+}
+
 fn generate_session_token() -> String {
     let mut b = [0u8; 32];
     OsRng.fill_bytes(&mut b);
@@ -614,12 +4224,71 @@ async fn get_admin_from_headers(state: &AppState, h: &HeaderMap) -> Result<Admin
     let t = get_session_token_from_headers(h).ok_or(StatusCode::UNAUTHORIZED)?;
     state
         .db
-        .get_admin_by_session_token(&t)
+        .get_admin_by_session_token(&t, state.config.session_ttl.as_secs() as i64)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::UNAUTHORIZED)
 }
 
+// For public endpoints that show more to a logged-in admin
+async fn is_admin(state: &AppState, h: &HeaderMap) -> bool {
+    get_admin_from_headers(state, h).await.is_ok()
+}
+
+// Resolves once the shutdown signal has been received
+async fn shutdown_requested(mut rx: watch::Receiver<Option<Instant>>) {
+    let _ = rx.wait_for(Option::is_some).await;
+}
+
+// A boundary this close after the startup ping is skipped, it would ping everything twice
+const STARTUP_PING_MIN_GAP_SECS: u64 = 120;
+
+// Seconds until the first interval-aligned cycle. Exactly on a boundary it runs right away.
+fn first_cycle_wait(now: u64, interval: u64, startup_pinged: bool) -> u64 {
+    let wait = (interval - now % interval) % interval;
+    if startup_pinged && wait < STARTUP_PING_MIN_GAP_SECS {
+        wait + interval
+    } else {
+        wait
+    }
+}
+
+// Returns true if shutdown was requested before `d` elapsed
+async fn sleep_unless_shutdown(d: Duration, rx: &mut watch::Receiver<Option<Instant>>) -> bool {
+    tokio::select! {
+        _ = sleep(d) => false,
+        _ = rx.wait_for(Option::is_some) => true,
+    }
+}
+
+// Runs the server until its graceful shutdown completes, but gives open connections
+// (long downloads, streams) at most `timeout` after the signal before dropping them.
+async fn serve_with_drain(
+    server: impl Future<Output = std::io::Result<()>>,
+    rx: watch::Receiver<Option<Instant>>,
+    timeout: Duration,
+) {
+    let mut server = std::pin::pin!(server);
+    let forced = tokio::select! {
+        res = &mut server => {
+            res.unwrap();
+            false
+        }
+        _ = async {
+            shutdown_requested(rx.clone()).await;
+            sleep(timeout).await;
+        } => true,
+    };
+    if let Some(started) = *rx.borrow() {
+        let took = started.elapsed().as_secs_f64();
+        if forced {
+            info!("Connections still open after {:.1}s, closing them.", took);
+        } else {
+            info!("Connections drained in {:.1}s.", took);
+        }
+    }
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c().await.unwrap();
@@ -635,3 +4304,155 @@ async fn shutdown_signal() {
     let term = std::future::pending::<()>();
     tokio::select! { _ = ctrl_c => {}, _ = term => {} }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn cors_preflight_for_create_server_is_answered() {
+        let origin = "https://dash.example.com";
+        let app = Router::new()
+            .route("/api/servers", post(|| async { StatusCode::IM_A_TEAPOT }))
+            .layer(cors_layer(&[origin.to_string()]).unwrap());
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/servers")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+            .body(Body::empty())
+            .unwrap();
+
+        let res = app.oneshot(preflight).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let h = res.headers();
+        assert_eq!(h[header::ACCESS_CONTROL_ALLOW_ORIGIN], origin);
+        assert_eq!(h[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        let methods = h[header::ACCESS_CONTROL_ALLOW_METHODS].to_str().unwrap();
+        assert!(methods.contains("POST"), "{methods}");
+    }
+
+    #[test]
+    fn validate_host_rejects_urls_whitespace_and_ports() {
+        assert!(validate_host("http://example.com").is_err());
+        assert!(validate_host("my server").is_err());
+        assert!(validate_host("example.com:25565").is_err());
+        assert!(validate_host("example.com/path").is_err());
+        assert!(validate_host("-bad-.example.com").is_err());
+        assert!(validate_host("   ").is_err());
+    }
+
+    #[test]
+    fn validate_host_accepts_hostnames_and_ip_literals() {
+        assert!(validate_host("play.example.com").is_ok());
+        assert!(validate_host("Play.Example.COM.").is_ok());
+        assert!(validate_host("localhost").is_ok());
+        assert!(validate_host("192.168.1.20").is_ok());
+        assert!(validate_host("2001:db8::1").is_ok());
+        assert!(validate_host("::1").is_ok());
+    }
+
+    fn online_ping(epoch: i64, players: i64) -> PingResult {
+        PingResult {
+            id: epoch,
+            server_id: 1,
+            pinged_at: epoch_to_rfc3339(epoch),
+            pinged_at_epoch: epoch,
+            online: true,
+            players_online: Some(players),
+            players_avg: None,
+            players_max: Some(20),
+            version: None,
+            motd: None,
+            parse_error: false,
+            players_online_raw: None,
+            compacted: false,
+            source: PingSource::Scheduled,
+            favicon_changed: false,
+            error_kind: None,
+            repeat_count: 0,
+            last_repeated_epoch: None,
+        }
+    }
+
+    #[test]
+    fn set_players_avg_rounds_half_up() {
+        let mut p = online_ping(0, 0);
+        set_players_avg(&mut p, 3, 2);
+        assert_eq!(p.players_online, Some(2));
+        assert_eq!(p.players_avg, Some(1.5));
+
+        set_players_avg(&mut p, 5, 2);
+        assert_eq!(p.players_online, Some(3));
+        set_players_avg(&mut p, 4, 3);
+        assert_eq!(p.players_online, Some(1));
+        assert_eq!(p.players_avg, Some(1.3));
+    }
+
+    #[test]
+    fn compress_segment_averages_a_chunk_of_one_and_two_players() {
+        // One long online segment that fits in a single chunk
+        let raw = vec![online_ping(0, 1), online_ping(3600, 2)];
+        let mut out = Vec::new();
+        compress_segment(&raw, 0, 1, true, 6 * 3600, 20 * 60, &mut out);
+
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].players_online, Some(2));
+        assert_eq!(out[0].players_avg, Some(1.5));
+    }
+
+    #[test]
+    fn first_cycle_wait_on_a_boundary() {
+        assert_eq!(first_cycle_wait(1_800_000, 600, false), 0);
+        // The startup ping just covered this boundary, wait for the next one
+        assert_eq!(first_cycle_wait(1_800_000, 600, true), 600);
+    }
+
+    #[test]
+    fn first_cycle_wait_just_before_a_boundary() {
+        // 60s is inside STARTUP_PING_MIN_GAP_SECS
+        let now = 1_800_000 + 600 - 60;
+        assert_eq!(first_cycle_wait(now, 600, false), 60);
+        assert_eq!(first_cycle_wait(now, 600, true), 660);
+    }
+
+    #[test]
+    fn first_cycle_wait_mid_interval() {
+        let now = 1_800_000 + 100;
+        assert_eq!(first_cycle_wait(now, 600, false), 500);
+        assert_eq!(first_cycle_wait(now, 600, true), 500);
+    }
+
+    #[test]
+    fn truncate_ellipsis_keeps_text_of_exactly_max_chars() {
+        let mut text = Some("hello".to_string());
+        truncate_ellipsis(&mut text, 5);
+        assert_eq!(text.as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn truncate_ellipsis_cuts_one_char_over() {
+        let mut text = Some("hello!".to_string());
+        truncate_ellipsis(&mut text, 5);
+        assert_eq!(text.as_deref(), Some("hell…"));
+    }
+
+    #[test]
+    fn truncate_ellipsis_counts_chars_not_bytes() {
+        let mut text = Some("§aété über".to_string());
+        truncate_ellipsis(&mut text, 6);
+        assert_eq!(text.as_deref(), Some("§aété…"));
+
+        // Multi-byte but within the limit, left alone
+        let mut text = Some("ééééé".to_string());
+        truncate_ellipsis(&mut text, 5);
+        assert_eq!(text.as_deref(), Some("ééééé"));
+
+        let mut none = None;
+        truncate_ellipsis(&mut none, 5);
+        assert_eq!(none, None);
+    }
+}
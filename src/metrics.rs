@@ -0,0 +1,99 @@
+// Prometheus text exposition for /api/metrics.
+// https://prometheus.io/docs/instrumenting/exposition_formats/
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Latency observations per server since the process started. Counts only ever grow,
+/// Prometheus derives rates and percentiles from the differences between scrapes.
+pub struct LatencyHistograms {
+    // Upper bounds in ms, ascending; `+Inf` is implied
+    bounds: Vec<f64>,
+    per_server: Mutex<HashMap<i64, Histogram>>,
+}
+
+#[derive(Clone)]
+struct Histogram {
+    // Non-cumulative, one slot per bound plus the overflow
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl LatencyHistograms {
+    pub fn new(bounds: Vec<f64>) -> Self {
+        Self {
+            bounds,
+            per_server: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn observe(&self, server_id: i64, latency_ms: f64) {
+        let slot = self.bounds.partition_point(|b| *b < latency_ms);
+        let mut per_server = self.per_server.lock().unwrap();
+        let h = per_server.entry(server_id).or_insert_with(|| Histogram {
+            counts: vec![0; self.bounds.len() + 1],
+            sum: 0.0,
+            count: 0,
+        });
+        h.counts[slot] += 1;
+        h.sum += latency_ms;
+        h.count += 1;
+    }
+
+    /// Forgets servers that aren't in `ids` anymore (deleted since their last ping).
+    pub fn retain(&self, ids: &[i64]) {
+        self.per_server
+            .lock()
+            .unwrap()
+            .retain(|id, _| ids.contains(id));
+    }
+
+    /// Renders the histograms of `servers` (id, name), in that order.
+    pub fn render(&self, servers: &[(i64, String)]) -> String {
+        let snapshot = self.per_server.lock().unwrap().clone();
+
+        let mut out = String::new();
+        out.push_str(
+            "# HELP minecraft_server_latency_ms Status ping round trip in milliseconds.\n",
+        );
+        out.push_str("# TYPE minecraft_server_latency_ms histogram\n");
+        for (id, name) in servers {
+            let Some(h) = snapshot.get(id) else {
+                continue;
+            };
+            let labels = format!("server_id=\"{}\",server=\"{}\"", id, escape_label(name));
+            let mut cumulative = 0;
+            for (bound, n) in self.bounds.iter().zip(&h.counts) {
+                cumulative += n;
+                let _ = writeln!(
+                    out,
+                    "minecraft_server_latency_ms_bucket{{{labels},le=\"{bound}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "minecraft_server_latency_ms_bucket{{{labels},le=\"+Inf\"}} {}",
+                h.count
+            );
+            let _ = writeln!(out, "minecraft_server_latency_ms_sum{{{labels}}} {}", h.sum);
+            let _ = writeln!(
+                out,
+                "minecraft_server_latency_ms_count{{{labels}}} {}",
+                h.count
+            );
+        }
+        out
+    }
+}
+
+// Label values are quoted, so backslashes, quotes and newlines need escaping
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
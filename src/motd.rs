@@ -0,0 +1,96 @@
+// MOTDs as readable text. Java servers send either a plain string or a chat component
+// (`{"text": ..., "extra": [...]}`), both may carry legacy `§` formatting codes.
+// https://minecraft.wiki/w/Text_component_format
+
+use serde_json::Value;
+
+/// Plain text of a status `description`, whatever shape it came in.
+pub fn from_description(description: &Value) -> String {
+    let mut out = String::new();
+    append_component(description, &mut out);
+    strip_formatting(&out)
+}
+
+// Depth-first, a component's own text comes before its `extra` children
+fn append_component(value: &Value, out: &mut String) {
+    match value {
+        Value::String(s) => out.push_str(s),
+        Value::Array(parts) => parts.iter().for_each(|p| append_component(p, out)),
+        Value::Object(map) => {
+            match map.get("text") {
+                Some(text) => append_component(text, out),
+                // Untranslated key is still better than nothing
+                None => {
+                    if let Some(Value::String(key)) = map.get("translate") {
+                        out.push_str(key);
+                    }
+                }
+            }
+            if let Some(extra) = map.get("extra") {
+                append_component(extra, out);
+            }
+        }
+        // Some servers send a bare number or bool as text
+        Value::Number(n) => out.push_str(&n.to_string()),
+        Value::Bool(b) => out.push_str(&b.to_string()),
+        Value::Null => {}
+    }
+}
+
+/// Drops `§x` color/style codes.
+pub fn strip_formatting(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn plain_string() {
+        assert_eq!(
+            from_description(&json!("A Minecraft Server")),
+            "A Minecraft Server"
+        );
+    }
+
+    #[test]
+    fn nested_component() {
+        let description = json!({
+            "text": "Welcome to ",
+            "extra": [
+                {"text": "Survival", "bold": true},
+                {"text": " | ", "extra": [{"text": "1.20"}]},
+                {"translate": "menu.online"}
+            ]
+        });
+        assert_eq!(
+            from_description(&description),
+            "Welcome to Survival | 1.20menu.online"
+        );
+    }
+
+    #[test]
+    fn section_sign_codes_are_stripped() {
+        assert_eq!(
+            from_description(&json!("§aGreen §lbold§r text")),
+            "Green bold text"
+        );
+        assert_eq!(
+            from_description(&json!({"text": "§6Gold", "extra": ["§7 gray"]})),
+            "Gold gray"
+        );
+        // A trailing lone § has nothing to format
+        assert_eq!(strip_formatting("end§"), "end");
+    }
+}
@@ -0,0 +1,71 @@
+// Strength rules for admin passwords, configured through PASSWORD_* env vars.
+
+use serde::Serialize;
+
+// Lowercased; checked case-insensitively. Not exhaustive, it only stops the obvious ones.
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "password",
+    "password1",
+    "password123",
+    "passw0rd",
+    "qwerty",
+    "qwerty123",
+    "qwertyuiop",
+    "abc123",
+    "111111",
+    "000000",
+    "iloveyou",
+    "letmein",
+    "welcome",
+    "welcome1",
+    "admin",
+    "admin123",
+    "administrator",
+    "changeme",
+    "change_me",
+    "secret",
+    "monkey",
+    "dragon",
+    "football",
+    "baseball",
+    "sunshine",
+    "princess",
+    "trustno1",
+    "minecraft",
+    "minecraft1",
+    "creeper",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub reject_common: bool,
+}
+
+impl PasswordPolicy {
+    /// `Err` carries a message that can be shown to the admin as-is.
+    pub fn check(&self, password: &str) -> Result<(), String> {
+        if password.chars().count() < self.min_length {
+            return Err(format!(
+                "password must be at least {} characters long",
+                self.min_length
+            ));
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            return Err("password must contain a digit".to_string());
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            return Err("password must contain a symbol".to_string());
+        }
+        if self.reject_common && COMMON_PASSWORDS.contains(&password.to_lowercase().as_str()) {
+            return Err("password is too common".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+// The player sample of a Java status response, cleaned up for display. Servers hide
+// players who opted out behind "Anonymous Player", and some list a player twice or
+// use the sample for extra MOTD lines with a zero UUID.
+
+use serde::{Deserialize, Serialize};
+
+const ANONYMOUS_NAME: &str = "Anonymous Player";
+const NIL_UUID: &str = "00000000-0000-0000-0000-000000000000";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplePlayer {
+    pub name: String,
+    pub id: String,
+}
+
+impl SamplePlayer {
+    /// Not a real player: the anonymous stand-in, a zero UUID or formatted text.
+    pub fn is_placeholder(&self) -> bool {
+        self.name == ANONYMOUS_NAME
+            || self.name.contains('§')
+            || self.id.is_empty()
+            || self.id == NIL_UUID
+    }
+}
+
+/// Drops repeated UUIDs (keeping the first entry) and, with `drop_placeholders`,
+/// entries that aren't real players.
+pub fn clean_sample(sample: Vec<SamplePlayer>, drop_placeholders: bool) -> Vec<SamplePlayer> {
+    let mut seen = std::collections::HashSet::new();
+    sample
+        .into_iter()
+        .filter(|p| !(drop_placeholders && p.is_placeholder()))
+        // Placeholders often share the zero UUID, dedup them by name instead
+        .filter(|p| {
+            let key = if p.is_placeholder() { &p.name } else { &p.id };
+            seen.insert(key.to_ascii_lowercase())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(name: &str, id: &str) -> SamplePlayer {
+        SamplePlayer {
+            name: name.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    fn names(sample: &[SamplePlayer]) -> Vec<&str> {
+        sample.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    const STEVE: &str = "8667ba71-b85a-4004-af54-457a9734eed7";
+    const ALEX: &str = "ec561538-f3fd-461d-aff5-086b22154bce";
+
+    #[test]
+    fn duplicate_uuids_keep_the_first_entry() {
+        let sample = vec![
+            player("Steve", STEVE),
+            player("Alex", ALEX),
+            player("Steve2", &STEVE.to_ascii_uppercase()),
+        ];
+        assert_eq!(names(&clean_sample(sample, false)), ["Steve", "Alex"]);
+    }
+
+    #[test]
+    fn placeholders_are_dropped_on_request() {
+        let sample = vec![
+            player("Steve", STEVE),
+            player(ANONYMOUS_NAME, STEVE),
+            player("§6Welcome!", NIL_UUID),
+            player("Nobody", NIL_UUID),
+        ];
+        assert_eq!(names(&clean_sample(sample, true)), ["Steve"]);
+    }
+
+    #[test]
+    fn placeholders_are_kept_and_deduped_by_name() {
+        let sample = vec![
+            player(ANONYMOUS_NAME, ALEX),
+            player(ANONYMOUS_NAME, STEVE),
+            player("§6Welcome!", NIL_UUID),
+            player("§6Rules: be nice", NIL_UUID),
+        ];
+        assert_eq!(
+            names(&clean_sample(sample, false)),
+            [ANONYMOUS_NAME, "§6Welcome!", "§6Rules: be nice"]
+        );
+    }
+}
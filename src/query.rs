@@ -0,0 +1,134 @@
+// Minecraft UDP Query protocol (GameSpy4), enabled with `enable-query=true` in server.properties.
+// Unlike the status ping it returns the full player list and the plugin string.
+// https://minecraft.wiki/w/Query
+
+use serde::Serialize;
+use std::io;
+use tokio::net::{UdpSocket, lookup_host};
+
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const TYPE_HANDSHAKE: u8 = 0x09;
+const TYPE_STAT: u8 = 0x00;
+// Servers only respond with session ids whose high nibbles are clear
+const SESSION_ID: i32 = 0x0102_0304 & 0x0F0F_0F0F;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryData {
+    pub players: Vec<String>,
+    // Server software, e.g. "Paper on 1.20.4", absent on vanilla
+    pub software: Option<String>,
+    pub plugins: Vec<String>,
+    pub map: Option<String>,
+}
+
+/// Runs a full stat query against `address:port`. The caller is expected to wrap this in a timeout.
+pub async fn full_stat(address: &str, port: u16) -> io::Result<QueryData> {
+    // Resolve first so the local socket can match the target's address family
+    let addr = lookup_host((address, port))
+        .await?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address for query host"))?;
+    let bind = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(addr).await?;
+    let mut buf = [0u8; 8192];
+
+    // 1. Handshake -> challenge token
+    socket.send(&request(TYPE_HANDSHAKE, &[])).await?;
+    let n = socket.recv(&mut buf).await?;
+    let token = parse_challenge(&buf[..n])?;
+
+    // 2. Full stat (the 4 trailing padding bytes are what make it "full")
+    let mut payload = token.to_be_bytes().to_vec();
+    payload.extend_from_slice(&[0, 0, 0, 0]);
+    socket.send(&request(TYPE_STAT, &payload)).await?;
+    let n = socket.recv(&mut buf).await?;
+
+    parse_full_stat(&buf[..n])
+}
+
+fn request(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(7 + payload.len());
+    out.extend_from_slice(&MAGIC);
+    out.push(kind);
+    out.extend_from_slice(&SESSION_ID.to_be_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// Response: type(1) session(4) token as a NUL-terminated decimal string
+fn parse_challenge(buf: &[u8]) -> io::Result<i32> {
+    if buf.len() < 6 || buf[0] != TYPE_HANDSHAKE {
+        return Err(invalid("bad handshake response"));
+    }
+    let text = std::str::from_utf8(&buf[5..])
+        .map_err(|_| invalid("challenge token is not UTF-8"))?
+        .trim_end_matches('\0');
+    text.parse::<i32>()
+        .map_err(|_| invalid("challenge token is not a number"))
+}
+
+// Response: type(1) session(4) "splitnum\0\x80\0" K\0V\0...\0 "\x01player_\0\0" name\0...\0
+fn parse_full_stat(buf: &[u8]) -> io::Result<QueryData> {
+    const KV_PADDING: usize = 11;
+
+    if buf.len() < 5 + KV_PADDING || buf[0] != TYPE_STAT {
+        return Err(invalid("bad stat response"));
+    }
+    let mut strings = buf[5 + KV_PADDING..].split(|b| *b == 0);
+
+    let mut software = None;
+    let mut plugins = Vec::new();
+    let mut map = None;
+    loop {
+        let key = strings
+            .next()
+            .ok_or_else(|| invalid("truncated key/value section"))?;
+        if key.is_empty() {
+            break;
+        }
+        let value = String::from_utf8_lossy(strings.next().unwrap_or_default()).into_owned();
+        match key {
+            b"plugins" => (software, plugins) = parse_plugins(&value),
+            b"map" => map = Some(value),
+            _ => {}
+        }
+    }
+
+    // Player section: "\x01player_" "" then one name per string, ended by an empty one
+    let players = strings
+        .skip_while(|s| *s != b"\x01player_")
+        .skip(2)
+        .take_while(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect();
+
+    Ok(QueryData {
+        players,
+        software,
+        plugins,
+        map,
+    })
+}
+
+// "Paper on 1.20.4: WorldEdit 7.2; Essentials 2.20" -> software + plugin list
+fn parse_plugins(value: &str) -> (Option<String>, Vec<String>) {
+    if value.is_empty() {
+        return (None, Vec::new());
+    }
+    match value.split_once(": ") {
+        Some((software, list)) => (
+            Some(software.to_string()),
+            list.split("; ").map(str::to_string).collect(),
+        ),
+        None => (Some(value.to_string()), Vec::new()),
+    }
+}
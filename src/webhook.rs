@@ -0,0 +1,61 @@
+// Alerts posted to a webhook. The body is Discord's (`{"content": ...}`), which Slack
+// incoming webhooks and most generic receivers accept as well.
+
+use reqwest::{Client, Url};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::warn;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_URL_LEN: usize = 2048;
+
+#[derive(Clone)]
+pub struct Notifier {
+    client: Client,
+    // WEBHOOK_URL, used for servers without their own
+    default_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    content: &'a str,
+}
+
+impl Notifier {
+    pub fn new(default_url: Option<String>) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(SEND_TIMEOUT)
+                .build()
+                .expect("TLS backend is compiled in"),
+            default_url,
+        }
+    }
+
+    /// Posts `message` to the server's own webhook, or the global one. Runs in the
+    /// background, a slow or failing receiver never holds up the pinger.
+    pub fn send(&self, server_url: Option<&str>, message: String) {
+        let Some(url) = server_url.or(self.default_url.as_deref()) else {
+            return;
+        };
+        let request = self.client.post(url).json(&Payload { content: &message });
+        tokio::spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => {}
+                Err(e) => warn!("Webhook delivery failed: {}", e.without_url()),
+            }
+        });
+    }
+}
+
+/// Checks a webhook URL before it's stored: absolute http(s) with a host.
+pub fn validate_url(url: &str) -> Result<(), &'static str> {
+    if url.len() > MAX_URL_LEN {
+        return Err("webhook_url is too long");
+    }
+    let parsed = Url::parse(url).map_err(|_| "webhook_url must be an absolute URL")?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err("webhook_url must be an http:// or https:// URL");
+    }
+    Ok(())
+}
@@ -9,6 +9,7 @@ let lastPingId = 0;
 let historyLabels = [];
 let historyPlayerData = [];
 let historyUptimeData = [];
+let historyIds = [];
 
 // Cache of servers so we do not refetch just to update highlight
 let serversCache = [];
@@ -37,6 +38,8 @@ function initDom() {
         playerCanvas: document.getElementById("playerChart"),
         uptimeCanvas: document.getElementById("uptimeChart"),
         rangeBtns: document.querySelectorAll(".btn-range"),
+        instanceName: document.getElementById("instance-name"),
+        staleWarning: document.getElementById("stale-warning"),
     };
 }
 
@@ -54,7 +57,12 @@ async function api(endpoint, method = "GET", body = null) {
 
     const res = await fetch(url, options);
     if (!res.ok) {
-        throw new Error(`API Error: ${res.status}`);
+        // JSON errors name the request, which is what to look for in the server log
+        const body = await res.json().catch(() => null);
+        const ref = body && body.request_id ? ` (request ${body.request_id})` : "";
+        const err = new Error(`API Error: ${res.status}${ref}`);
+        err.status = res.status;
+        throw err;
     }
     return res.json();
 }
@@ -64,6 +72,7 @@ function resetHistory() {
     historyLabels = [];
     historyPlayerData = [];
     historyUptimeData = [];
+    historyIds = [];
 }
 
 // --- Auth ---
@@ -78,6 +87,38 @@ async function checkAuth() {
     }
 }
 
+async function loadInstanceInfo() {
+    try {
+        const info = await api("/info");
+        if (info.name && dom.instanceName) {
+            dom.instanceName.textContent = info.name;
+            document.title = `${info.name} - Hodr`;
+        }
+    } catch (_) {
+        // Header just stays plain
+    }
+}
+
+// Hides what this deployment has switched off
+async function loadFeatures() {
+    try {
+        const features = await api("/features");
+        if (!features.login && dom.navAuthBtn) dom.navAuthBtn.hidden = true;
+    } catch (_) {
+        // Show everything, the API answers 403 for what's disabled
+    }
+}
+
+// Shown when the server-side pinger hasn't finished a cycle for a while
+async function checkPingerHealth() {
+    try {
+        const stats = await api("/stats");
+        if (dom.staleWarning) dom.staleWarning.hidden = stats.pinger_healthy !== false;
+    } catch (_) {
+        // Keep whatever was shown last
+    }
+}
+
 function enableAdminMode() {
     if (!dom.navAuthBtn) return;
 
@@ -105,6 +146,8 @@ function renderServerList(servers) {
     serversCache.forEach((s) => {
         const el = document.createElement("div");
         el.className = `server-item ${selectedServerId == s.id ? "active" : ""}`;
+        // Validated "#rrggbb" from the API, null keeps the theme default
+        if (s.color) el.style.borderLeftColor = s.color;
 
         const info = document.createElement("div");
         info.className = "server-info";
@@ -121,7 +164,8 @@ function renderServerList(servers) {
         info.appendChild(ip);
 
         const dot = document.createElement("div");
-        dot.className = `server-status-dot ${s.last_online ? "status-online" : "status-offline"}`;
+        dot.className = `server-status-dot status-${s.status || (s.last_online ? "online" : "offline")}`;
+        if (s.status === "unknown") dot.title = "Pending, not pinged yet";
 
         el.appendChild(info);
         el.appendChild(dot);
@@ -138,11 +182,17 @@ function renderServerList(servers) {
     dom.list.scrollTop = scrollPos;
 }
 
+// isOnline = null: nothing to go on yet (no pings in the range)
 function updateStats(isOnline, players, pingCount) {
     if (!dom.statStatus || !dom.statPlayers || !dom.statPings) return;
 
-    dom.statStatus.textContent = isOnline ? "ONLINE" : "OFFLINE";
-    dom.statStatus.style.color = isOnline ? "var(--color-10)" : "var(--danger)";
+    if (isOnline === null) {
+        dom.statStatus.textContent = "PENDING";
+        dom.statStatus.style.color = "var(--text-muted)";
+    } else {
+        dom.statStatus.textContent = isOnline ? "ONLINE" : "OFFLINE";
+        dom.statStatus.style.color = isOnline ? "var(--color-10)" : "var(--danger)";
+    }
     dom.statPlayers.textContent = isOnline ? players : "--";
     dom.statPings.textContent = pingCount;
 
@@ -164,6 +214,11 @@ async function loadServers() {
             renderServerList(); // reuse cache, no extra /servers call
         }
     } catch (e) {
+        // Instance requires a login even to look (AUTH_REQUIRED_FOR_READS)
+        if (e.status === 401) {
+            window.location.href = "login.html";
+            return;
+        }
         console.error("Error loading servers", e);
     }
 }
@@ -205,26 +260,41 @@ async function refreshDashboard() {
         }
 
         if (Array.isArray(newPings) && newPings.length > 0) {
+            // A collapsed outage comes again under the same id once it has grown,
+            // replace the points we already have for it
+            if (lastPingId && newPings.some((h) => h.id === lastPingId)) {
+                while (historyIds.length > 0 && historyIds[historyIds.length - 1] === lastPingId) {
+                    historyIds.pop();
+                    historyLabels.pop();
+                    historyPlayerData.pop();
+                    historyUptimeData.pop();
+                }
+            }
+
             for (const h of newPings) {
                 const label = new Date(h.pinged_at).toLocaleTimeString([], {
                     hour: "2-digit",
                     minute: "2-digit",
                 });
 
+                // Averaged points carry the unrounded value as well
                 const playersField =
-                    h.player_count !== undefined && h.player_count !== null
-                        ? h.player_count
-                        : h.players_online !== undefined && h.players_online !== null
-                            ? h.players_online
-                            : h.online
-                                ? 1
-                                : 0;
+                    typeof h.players_avg === "number"
+                        ? h.players_avg
+                        : h.player_count !== undefined && h.player_count !== null
+                            ? h.player_count
+                            : h.players_online !== undefined && h.players_online !== null
+                                ? h.players_online
+                                : h.online
+                                    ? 1
+                                    : 0;
 
                 const uptime = h.online ? 1 : 0;
 
                 historyLabels.push(label);
                 historyPlayerData.push(playersField);
                 historyUptimeData.push(uptime);
+                historyIds.push(h.id);
 
                 if (typeof h.id === "number" && h.id > lastPingId) {
                     lastPingId = h.id;
@@ -237,11 +307,12 @@ async function refreshDashboard() {
                 historyLabels.splice(0, excess);
                 historyPlayerData.splice(0, excess);
                 historyUptimeData.splice(0, excess);
+                historyIds.splice(0, excess);
             }
         }
 
         if (historyLabels.length === 0) {
-            updateStats(false, 0, 0);
+            updateStats(null, 0, 0);
 
             if (playerChart) {
                 playerChart.data.labels = [];
@@ -270,12 +341,23 @@ async function refreshDashboard() {
 }
 
 // --- Charts ---
+const DEFAULT_ACCENT = "#84cc16";
+
+// Line color for the selected server's charts
+function selectedAccent() {
+    const s = serversCache.find((s) => s.id == selectedServerId);
+    return (s && s.color) || DEFAULT_ACCENT;
+}
+
 function updatePlayerChart(labels, dataPoints) {
     if (!dom.playerCanvas) return;
 
+    const accent = selectedAccent();
     if (playerChart) {
         playerChart.data.labels = labels;
         playerChart.data.datasets[0].data = dataPoints;
+        playerChart.data.datasets[0].borderColor = accent;
+        playerChart.data.datasets[0].pointHoverBackgroundColor = accent;
         playerChart.update("none");
         return;
     }
@@ -293,14 +375,14 @@ function updatePlayerChart(labels, dataPoints) {
                 {
                     label: "Players",
                     data: dataPoints,
-                    borderColor: "#84cc16",
+                    borderColor: accent,
                     backgroundColor: gradient,
                     borderWidth: 2,
                     fill: true,
                     tension: 0.3,
                     pointRadius: 0,
                     pointHoverRadius: 4,
-                    pointHoverBackgroundColor: "#84cc16",
+                    pointHoverBackgroundColor: accent,
                 },
             ],
         },
@@ -431,6 +513,7 @@ function scheduleAlignedRefresh() {
     }
 
     setTimeout(async function tick() {
+        checkPingerHealth();
         try {
             const servers = await api("/servers");
             renderServerList(servers);
@@ -443,6 +526,7 @@ function scheduleAlignedRefresh() {
         }
 
         setInterval(async () => {
+            checkPingerHealth();
             try {
                 const servers = await api("/servers");
                 renderServerList(servers);
@@ -465,9 +549,13 @@ function setupEventListeners() {
             const formData = new FormData(dom.form);
 
             try {
+                const port = formData.get("port");
                 await api("/servers", "POST", {
                     name: formData.get("name"),
                     address: formData.get("address"),
+                    edition: formData.get("edition"),
+                    // Left empty, the server picks 25565 (Java) or 19132 (Bedrock)
+                    ...(port ? { port: Number(port) } : {}),
                 });
                 dom.form.reset();
                 await loadServers();
@@ -542,6 +630,9 @@ async function init() {
     setupEventListeners();
 
     await checkAuth();
+    loadInstanceInfo();
+    loadFeatures();
+    checkPingerHealth();
     await loadServers();        // initial data
     scheduleAlignedRefresh();   // aligned updates every 10 minutes
 }